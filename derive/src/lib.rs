@@ -0,0 +1,574 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse::Parser, parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error,
+    Fields, Ident, ImplItem, ItemImpl, LitInt, LitStr, Path, Result, Type,
+};
+
+// 32 bit FNV-1a like `framework::type_id_from_name`, ids derived from names must never change
+fn name_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[derive(Default)]
+struct TypeAttribute {
+    type_id: Option<u32>,
+    name: Option<String>,
+    revision: Option<u32>,
+}
+
+impl TypeAttribute {
+    fn parse_meta(&mut self, meta: syn::meta::ParseNestedMeta, revision: bool) -> Result<()> {
+        if meta.path.is_ident("type_id") {
+            self.type_id = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+        } else if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if revision && meta.path.is_ident("revision") {
+            self.revision = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+        } else if revision {
+            return Err(meta.error("expected `type_id`, `name` or `revision`"));
+        } else {
+            return Err(meta.error("expected `type_id` or `name`"));
+        }
+
+        Ok(())
+    }
+
+    // name defaults to the identifier, the id to a hash of the name
+    fn resolve(self, ident: &Ident) -> (u32, String, u32) {
+        let name = self.name.unwrap_or_else(|| ident.to_string());
+        let type_id = self.type_id.unwrap_or_else(|| name_hash(&name));
+
+        (type_id, name, self.revision.unwrap_or(1))
+    }
+}
+
+fn event_attribute(attrs: &[Attribute]) -> Result<TypeAttribute> {
+    let mut event = TypeAttribute::default();
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("event")) {
+        attr.parse_nested_meta(|meta| event.parse_meta(meta, true))?;
+    }
+
+    Ok(event)
+}
+
+// Implements `Event`. Each variant (or the struct) is described by
+// `#[event(type_id = N, name = "..", revision = N)]`, all optional. The name defaults to the
+// identifier and the id to a hash of the name, so reordering or adding variants never renumbers
+// events, and a renamed variant keeps its id by pinning its old name.
+#[proc_macro_derive(Event, attributes(event))]
+pub fn derive_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_event(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_event(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // pattern matching the event type, with its id, name and revision
+    let mut types: Vec<(TokenStream2, u32, String, u32)> = Vec::new();
+
+    match &input.data {
+        Data::Enum(data) => {
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                let (type_id, type_name, revision) =
+                    event_attribute(&variant.attrs)?.resolve(ident);
+
+                if let Some((_, _, other, _)) = types.iter().find(|(_, id, _, _)| *id == type_id) {
+                    return Err(Error::new(
+                        ident.span(),
+                        format!("event type id {} is already used by `{}`", type_id, other),
+                    ));
+                }
+                if types.iter().any(|(_, _, other, _)| *other == type_name) {
+                    return Err(Error::new(
+                        ident.span(),
+                        format!("event type name `{}` is already used", type_name),
+                    ));
+                }
+
+                types.push((quote! { Self::#ident { .. } }, type_id, type_name, revision));
+            }
+        }
+        Data::Struct(_) => {
+            let (type_id, type_name, revision) = event_attribute(&input.attrs)?.resolve(name);
+
+            types.push((quote! { _ }, type_id, type_name, revision));
+        }
+        Data::Union(_) => return Err(Error::new(name.span(), "unions are not supported")),
+    }
+
+    let type_id_arms = types
+        .iter()
+        .map(|(pattern, type_id, _, _)| quote! { #pattern => #type_id, });
+    let type_name_arms = types
+        .iter()
+        .map(|(pattern, _, type_name, _)| quote! { #pattern => #type_name, });
+    let revision_arms = types
+        .iter()
+        .map(|(pattern, _, _, revision)| quote! { #pattern => #revision, });
+    let event_types = types.iter().map(|(_, type_id, type_name, revision)| {
+        quote! {
+            ::framework::EventType {
+                id: #type_id,
+                name: #type_name,
+                revision: #revision,
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::framework::Event for #name #ty_generics #where_clause {
+            fn type_id(&self) -> ::framework::EventTypeId {
+                match self {
+                    #(#type_id_arms)*
+                }
+            }
+
+            fn type_name(&self) -> &'static str {
+                match self {
+                    #(#type_name_arms)*
+                }
+            }
+
+            fn revision(&self) -> u32 {
+                match self {
+                    #(#revision_arms)*
+                }
+            }
+
+            fn event_types() -> &'static [::framework::EventType] {
+                &[#(#event_types),*]
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct CommandAttribute {
+    aggregate: Option<Path>,
+    expected_state: Option<Ident>,
+}
+
+fn command_attribute(attrs: &[Attribute]) -> Result<CommandAttribute> {
+    let mut command = CommandAttribute::default();
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("command")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("aggregate") {
+                command.aggregate = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("expected_state") {
+                command.expected_state = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `aggregate` or `expected_state`"))
+            }
+        })?;
+    }
+
+    Ok(command)
+}
+
+// pattern binding the `#[aggregate_id]` field of `fields` to `id`
+fn aggregate_id_pattern(fields: &Fields, span: Span) -> Result<TokenStream2> {
+    let mut marked = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.attrs.iter().any(|a| a.path().is_ident("aggregate_id")));
+
+    let Some((index, field)) = marked.next() else {
+        return Err(Error::new(
+            span,
+            "expected a field marked `#[aggregate_id]`",
+        ));
+    };
+    if let Some((_, other)) = marked.next() {
+        return Err(Error::new(
+            other.span(),
+            "only one field can be marked `#[aggregate_id]`",
+        ));
+    }
+
+    Ok(match &field.ident {
+        Some(ident) => quote! { { #ident: id, .. } },
+        None => {
+            let index = syn::Index::from(index);
+            quote! { { #index: id, .. } }
+        }
+    })
+}
+
+// Implements `Command` for the aggregate named by `#[command(aggregate = ..)]`, reading the id
+// from the field marked `#[aggregate_id]`. `#[command(expected_state = ..)]` on the type or on
+// enum variants sets the `ExpectedState` variant.
+#[proc_macro_derive(Command, attributes(command, aggregate_id))]
+pub fn derive_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_command(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_command(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let attribute = command_attribute(&input.attrs)?;
+    let Some(aggregate) = attribute.aggregate else {
+        return Err(Error::new(
+            name.span(),
+            "expected `#[command(aggregate = ..)]`",
+        ));
+    };
+    let default_state = attribute
+        .expected_state
+        .unwrap_or_else(|| Ident::new("Any", Span::call_site()));
+
+    let (aggregate_id, expected_state) = match &input.data {
+        Data::Enum(data) => {
+            let mut id_arms = Vec::new();
+            let mut state_arms = Vec::new();
+
+            for variant in &data.variants {
+                let ident = &variant.ident;
+
+                let pattern = aggregate_id_pattern(&variant.fields, ident.span())?;
+                id_arms.push(quote! { Self::#ident #pattern => *id, });
+
+                let state = command_attribute(&variant.attrs)?
+                    .expected_state
+                    .unwrap_or_else(|| default_state.clone());
+                state_arms.push(quote! {
+                    Self::#ident { .. } => ::framework::ExpectedState::#state,
+                });
+            }
+
+            (
+                quote! { match self { #(#id_arms)* } },
+                quote! { match self { #(#state_arms)* } },
+            )
+        }
+        Data::Struct(data) => {
+            let pattern = aggregate_id_pattern(&data.fields, name.span())?;
+
+            (
+                quote! {
+                    let Self #pattern = self;
+                    *id
+                },
+                quote! { ::framework::ExpectedState::#default_state },
+            )
+        }
+        Data::Union(_) => return Err(Error::new(name.span(), "unions are not supported")),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::framework::Command for #name #ty_generics #where_clause {
+            type Aggregate = #aggregate;
+
+            fn aggregate_id(&self) -> u64 {
+                #aggregate_id
+            }
+
+            fn expected_state(&self) -> ::framework::ExpectedState {
+                #expected_state
+            }
+        }
+    })
+}
+
+// Placed on `impl Aggregate for ..`, adds `type_id()` and `type_name()` from
+// `#[aggregate(type_id = N, name = "..")]`. The name defaults to the type's identifier and the id
+// to a hash of the name.
+#[proc_macro_attribute]
+pub fn aggregate(args: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+
+    expand_aggregate(args.into(), item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_aggregate(args: TokenStream2, mut item: ItemImpl) -> Result<TokenStream2> {
+    let mut attribute = TypeAttribute::default();
+    syn::meta::parser(|meta| attribute.parse_meta(meta, false)).parse2(args)?;
+
+    if let Some(existing) = item.items.iter().find_map(|i| match i {
+        ImplItem::Fn(f) if f.sig.ident == "type_id" || f.sig.ident == "type_name" => Some(f),
+        _ => None,
+    }) {
+        return Err(Error::new(
+            existing.sig.ident.span(),
+            format!("`{}` is provided by `#[aggregate]`", existing.sig.ident),
+        ));
+    }
+
+    let Type::Path(self_ty) = &*item.self_ty else {
+        return Err(Error::new(item.self_ty.span(), "expected a named type"));
+    };
+    let ident = &self_ty
+        .path
+        .segments
+        .last()
+        .ok_or_else(|| Error::new(self_ty.span(), "expected a named type"))?
+        .ident;
+    let (type_id, type_name, _) = attribute.resolve(ident);
+
+    item.items.push(syn::parse_quote! {
+        fn type_id() -> ::framework::AggregateTypeId
+        where
+            Self: Sized,
+        {
+            #type_id
+        }
+    });
+    item.items.push(syn::parse_quote! {
+        fn type_name() -> &'static str
+        where
+            Self: Sized,
+        {
+            #type_name
+        }
+    });
+
+    Ok(quote! { #item })
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    // published FNV-1a test vectors and names used as ids, these must never change
+    const HASHES: &[(&str, u32)] = &[
+        ("", 0x811c_9dc5),
+        ("a", 0xe40c_292c),
+        ("foobar", 0xbf9c_f968),
+        ("Added", 0xb35c_3185),
+        ("Renamed", 0x78c0_10b5),
+    ];
+
+    #[test]
+    fn name_hash_is_stable() {
+        for &(name, hash) in HASHES {
+            assert_eq!(name_hash(name), hash, "{:?}", name);
+            assert_eq!(framework::type_id_from_name(name), hash, "{:?}", name);
+        }
+    }
+
+    fn error(result: Result<TokenStream2>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn event_expansion() {
+        let expanded = expand_event(parse_quote! {
+            enum TestEvent {
+                Added(u64),
+                #[event(type_id = 7, name = "Renamed", revision = 2)]
+                Retitled { name: String },
+            }
+        })
+        .unwrap();
+
+        let added = 0xb35c_3185_u32;
+        let expected = quote! {
+            impl ::framework::Event for TestEvent {
+                fn type_id(&self) -> ::framework::EventTypeId {
+                    match self {
+                        Self::Added { .. } => #added,
+                        Self::Retitled { .. } => 7u32,
+                    }
+                }
+
+                fn type_name(&self) -> &'static str {
+                    match self {
+                        Self::Added { .. } => "Added",
+                        Self::Retitled { .. } => "Renamed",
+                    }
+                }
+
+                fn revision(&self) -> u32 {
+                    match self {
+                        Self::Added { .. } => 1u32,
+                        Self::Retitled { .. } => 2u32,
+                    }
+                }
+
+                fn event_types() -> &'static [::framework::EventType] {
+                    &[
+                        ::framework::EventType { id: #added, name: "Added", revision: 1u32, },
+                        ::framework::EventType { id: 7u32, name: "Renamed", revision: 2u32, }
+                    ]
+                }
+            }
+        };
+        assert_eq!(expanded.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn event_errors() {
+        assert_eq!(
+            error(expand_event(parse_quote! {
+                enum TestEvent {
+                    #[event(type_id = 1)]
+                    Added,
+                    #[event(type_id = 1)]
+                    Removed,
+                }
+            })),
+            "event type id 1 is already used by `Added`"
+        );
+        assert_eq!(
+            error(expand_event(parse_quote! {
+                enum TestEvent {
+                    Added,
+                    // the id is pinned, only the name collides
+                    #[event(type_id = 2, name = "Added")]
+                    Removed,
+                }
+            })),
+            "event type name `Added` is already used"
+        );
+        assert_eq!(
+            error(expand_event(parse_quote! {
+                #[event(version = 2)]
+                struct TestEvent;
+            })),
+            "expected `type_id`, `name` or `revision`"
+        );
+        assert_eq!(
+            error(expand_event(parse_quote! {
+                union TestEvent { a: u32 }
+            })),
+            "unions are not supported"
+        );
+    }
+
+    #[test]
+    fn command_expansion() {
+        let expanded = expand_command(parse_quote! {
+            #[command(aggregate = TestAggregate, expected_state = Exists)]
+            enum TestCommand {
+                #[command(expected_state = New)]
+                Create(#[aggregate_id] u64, String),
+                Rename { #[aggregate_id] id: u64, name: String },
+            }
+        })
+        .unwrap();
+
+        let expected = quote! {
+            impl ::framework::Command for TestCommand {
+                type Aggregate = TestAggregate;
+
+                fn aggregate_id(&self) -> u64 {
+                    match self {
+                        Self::Create { 0: id, .. } => *id,
+                        Self::Rename { id: id, .. } => *id,
+                    }
+                }
+
+                fn expected_state(&self) -> ::framework::ExpectedState {
+                    match self {
+                        Self::Create { .. } => ::framework::ExpectedState::New,
+                        Self::Rename { .. } => ::framework::ExpectedState::Exists,
+                    }
+                }
+            }
+        };
+        assert_eq!(expanded.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn command_errors() {
+        assert_eq!(
+            error(expand_command(parse_quote! {
+                struct TestCommand { #[aggregate_id] id: u64 }
+            })),
+            "expected `#[command(aggregate = ..)]`"
+        );
+        assert_eq!(
+            error(expand_command(parse_quote! {
+                #[command(aggregate = TestAggregate)]
+                struct TestCommand { id: u64 }
+            })),
+            "expected a field marked `#[aggregate_id]`"
+        );
+        assert_eq!(
+            error(expand_command(parse_quote! {
+                #[command(aggregate = TestAggregate)]
+                struct TestCommand { #[aggregate_id] a: u64, #[aggregate_id] b: u64 }
+            })),
+            "only one field can be marked `#[aggregate_id]`"
+        );
+    }
+
+    #[test]
+    fn aggregate_expansion() {
+        let expanded = expand_aggregate(
+            quote! { name = "Renamed" },
+            parse_quote! {
+                impl Aggregate for TestAggregate {
+                    type Event = TestEvent;
+                }
+            },
+        )
+        .unwrap();
+
+        let renamed = 0x78c0_10b5_u32;
+        let expected = quote! {
+            impl Aggregate for TestAggregate {
+                type Event = TestEvent;
+
+                fn type_id() -> ::framework::AggregateTypeId
+                where
+                    Self: Sized,
+                {
+                    #renamed
+                }
+
+                fn type_name() -> &'static str
+                where
+                    Self: Sized,
+                {
+                    "Renamed"
+                }
+            }
+        };
+        assert_eq!(expanded.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn aggregate_errors() {
+        assert_eq!(
+            error(expand_aggregate(
+                quote! { revision = 2 },
+                parse_quote! { impl Aggregate for TestAggregate {} },
+            )),
+            "expected `type_id` or `name`"
+        );
+        assert_eq!(
+            error(expand_aggregate(
+                quote! {},
+                parse_quote! {
+                    impl Aggregate for TestAggregate {
+                        fn type_id() -> u32 { 1 }
+                    }
+                },
+            )),
+            "`type_id` is provided by `#[aggregate]`"
+        );
+    }
+}
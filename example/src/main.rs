@@ -1,75 +1,70 @@
-use std::{collections::HashMap, sync::Mutex};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    marker::PhantomData,
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use thiserror::Error;
 
 use framework::{
-    Aggregate, AggregateTypeId, Command, DummySnapshotStore, Event, EventStore, EventTypeId,
-    Framework, Query, QueryHandler, ReadModel, ReadModelStore, Result,
+    aggregate, Aggregate, AggregateTypeId, Cipher, Codec, CodecId, Command, CommandBus,
+    DummySnapshotStore, Event, EventStore, Framework, FrameworkError, JsonCodec, Page, Query,
+    QueryBus, QueryHandler, ReadModel, ReadModelScan, ReadModelStore, ReadModelStoreRef,
+    ReadModelUpdate, Result, Saga, SagaStore, SagaTypeId, VersionedEvent,
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+// ids are pinned, they are stored with every event
+#[derive(Serialize, Deserialize, Debug, Event)]
 enum EmployeeEvent {
+    #[event(type_id = 1)]
     EmployeeCreated {
-        version: u32,
         id: u64,
         name: String,
         address: String,
     },
-    NameChanged {
-        version: u32,
-        name: String,
-    },
-    AddressChanged {
-        version: u32,
-        address: String,
-    },
-}
-
-impl Event for EmployeeEvent {
-    fn type_id(&self) -> EventTypeId {
-        match self {
-            EmployeeEvent::EmployeeCreated { .. } => 1,
-            EmployeeEvent::NameChanged { .. } => 2,
-            EmployeeEvent::AddressChanged { .. } => 3,
-        }
-    }
-
-    fn version(&self) -> u32 {
-        match self {
-            EmployeeEvent::EmployeeCreated { version, .. } => *version,
-            EmployeeEvent::NameChanged { version, .. } => *version,
-            EmployeeEvent::AddressChanged { version, .. } => *version,
-        }
-    }
+    #[event(type_id = 2)]
+    NameChanged { name: String },
+    #[event(type_id = 3)]
+    AddressChanged { address: String },
+    #[event(type_id = 4)]
+    EmployeeDeleted,
 }
 
+#[derive(Deserialize, Command)]
+#[command(aggregate = EmployeeAggregate, expected_state = Exists)]
 enum EmployeeCommand {
+    #[command(expected_state = NotExists)]
     CreateEmployee {
+        #[aggregate_id]
         id: u64,
         name: String,
         address: String,
     },
     ChangeName {
+        #[aggregate_id]
         id: u64,
         name: String,
     },
     ChangeAddress {
+        #[aggregate_id]
         id: u64,
         address: String,
     },
+    DeleteEmployee {
+        #[aggregate_id]
+        id: u64,
+    },
 }
 
-impl Command for EmployeeCommand {
-    type Aggregate = EmployeeAggregate;
-
-    fn aggregate_id(&self) -> u64 {
-        match self {
-            EmployeeCommand::CreateEmployee { id, .. } => *id,
-            EmployeeCommand::ChangeName { id, .. } => *id,
-            EmployeeCommand::ChangeAddress { id, .. } => *id,
-        }
-    }
+#[derive(Error, Debug)]
+enum EmployeeError {
+    #[error("Employee name must not be empty")]
+    EmptyName,
+    #[error("Employee is deleted")]
+    Deleted,
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -77,71 +72,57 @@ struct EmployeeAggregate {
     id: u64,
     name: String,
     address: String,
-    version: u32,
+    deleted: bool,
 }
 
+#[aggregate(type_id = 1)]
 impl Aggregate for EmployeeAggregate {
     type Command = EmployeeCommand;
     type Event = EmployeeEvent;
+    type Error = EmployeeError;
 
-    fn type_id() -> AggregateTypeId
-    where
-        Self: Sized,
-    {
-        1
-    }
-
-    fn version(&self) -> u32 {
-        self.version
-    }
+    fn handle(&self, command: Self::Command) -> std::result::Result<Vec<Self::Event>, Self::Error> {
+        if self.deleted {
+            return Err(EmployeeError::Deleted);
+        }
 
-    fn handle(&self, command: Self::Command) -> Result<Vec<Self::Event>> {
         match command {
+            EmployeeCommand::CreateEmployee { name, .. }
+            | EmployeeCommand::ChangeName { name, .. }
+                if name.is_empty() =>
+            {
+                Err(EmployeeError::EmptyName)
+            }
             EmployeeCommand::CreateEmployee { id, name, address } => {
-                Ok(vec![EmployeeEvent::EmployeeCreated {
-                    version: 1,
-                    id,
-                    name,
-                    address,
-                }])
+                Ok(vec![EmployeeEvent::EmployeeCreated { id, name, address }])
+            }
+            EmployeeCommand::ChangeName { name, .. } => {
+                Ok(vec![EmployeeEvent::NameChanged { name }])
             }
-            EmployeeCommand::ChangeName { name, .. } => Ok(vec![EmployeeEvent::NameChanged {
-                version: self.version + 1,
-                name,
-            }]),
             EmployeeCommand::ChangeAddress { address, .. } => {
-                Ok(vec![EmployeeEvent::AddressChanged {
-                    version: self.version + 1,
-                    address,
-                }])
+                Ok(vec![EmployeeEvent::AddressChanged { address }])
             }
+            EmployeeCommand::DeleteEmployee { .. } => Ok(vec![EmployeeEvent::EmployeeDeleted]),
         }
     }
 
     fn apply_events(&mut self, events: Vec<Self::Event>) -> Result<()> {
         for event in events {
             match event {
-                EmployeeEvent::EmployeeCreated {
-                    version,
-                    id,
-                    name,
-                    address,
-                } => {
-                    self.version = version;
+                EmployeeEvent::EmployeeCreated { id, name, address } => {
                     self.id = id;
                     self.name = name;
                     self.address = address;
                 }
-                EmployeeEvent::NameChanged { version, name, .. } => {
-                    self.version = version;
+                EmployeeEvent::NameChanged { name } => {
                     self.name = name;
                 }
-                EmployeeEvent::AddressChanged {
-                    version, address, ..
-                } => {
-                    self.version = version;
+                EmployeeEvent::AddressChanged { address } => {
                     self.address = address;
                 }
+                EmployeeEvent::EmployeeDeleted => {
+                    self.deleted = true;
+                }
             }
         }
 
@@ -149,7 +130,7 @@ impl Aggregate for EmployeeAggregate {
     }
 }
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize)]
 struct EmployeeReadModel {
     id: u64,
     name: String,
@@ -159,7 +140,7 @@ struct EmployeeReadModel {
 impl ReadModel for EmployeeReadModel {
     type Event = EmployeeEvent;
 
-    fn apply_event(&mut self, event: &Self::Event) -> Result<()> {
+    fn apply_event(&mut self, event: &Self::Event) -> Result<ReadModelUpdate> {
         match event {
             EmployeeEvent::EmployeeCreated {
                 id, name, address, ..
@@ -174,12 +155,35 @@ impl ReadModel for EmployeeReadModel {
             EmployeeEvent::AddressChanged { address, .. } => {
                 self.address = address.clone();
             }
+            EmployeeEvent::EmployeeDeleted => return Ok(ReadModelUpdate::Delete),
         }
 
-        Ok(())
+        Ok(ReadModelUpdate::Upsert)
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+struct NameHistoryReadModel {
+    names: Vec<String>,
+}
+
+impl ReadModel for NameHistoryReadModel {
+    type Event = EmployeeEvent;
+
+    fn apply_event(&mut self, event: &Self::Event) -> Result<ReadModelUpdate> {
+        match event {
+            EmployeeEvent::EmployeeCreated { name, .. } | EmployeeEvent::NameChanged { name } => {
+                self.names.push(name.clone());
+            }
+            EmployeeEvent::AddressChanged { .. } => {}
+            EmployeeEvent::EmployeeDeleted => return Ok(ReadModelUpdate::Delete),
+        }
+
+        Ok(ReadModelUpdate::Upsert)
     }
 }
 
+#[derive(Deserialize)]
 struct EmployeeQuery {
     id: u64,
 }
@@ -194,45 +198,317 @@ impl QueryHandler<EmployeeQuery> for EmployeeQueryHandler {
     type ReadModelStore = ReadModelStoreImpl;
     type Output = Option<EmployeeReadModel>;
     async fn handle(
-        read_model_store: &ReadModelStoreImpl,
+        read_model_store: ReadModelStoreRef<'_, ReadModelStoreImpl>,
         query: EmployeeQuery,
     ) -> Result<Option<EmployeeReadModel>> {
         read_model_store.read(query.id).await
     }
 }
 
+struct EmployeeDetailsQuery {
+    id: u64,
+}
+
+impl Query for EmployeeDetailsQuery {
+    type Handler = EmployeeDetailsQueryHandler;
+}
+
+struct EmployeeDetailsQueryHandler;
+
+impl QueryHandler<EmployeeDetailsQuery> for EmployeeDetailsQueryHandler {
+    type ReadModelStore = (ReadModelStoreImpl, NameHistoryStoreImpl);
+    type Output = Option<(EmployeeReadModel, NameHistoryReadModel)>;
+    async fn handle(
+        read_model_store: ReadModelStoreRef<'_, (ReadModelStoreImpl, NameHistoryStoreImpl)>,
+        query: EmployeeDetailsQuery,
+    ) -> Result<Option<(EmployeeReadModel, NameHistoryReadModel)>> {
+        let (employees, name_histories) = *read_model_store;
+
+        let Some(employee) = employees.read(query.id).await? else {
+            return Ok(None);
+        };
+        let name_history = name_histories.read(query.id).await?.unwrap_or_default();
+
+        Ok(Some((employee, name_history)))
+    }
+}
+
+struct EmployeesQuery {
+    cursor: Option<u64>,
+    limit: usize,
+}
+
+impl Query for EmployeesQuery {
+    type Handler = EmployeesQueryHandler;
+}
+
+struct EmployeesQueryHandler;
+
+impl QueryHandler<EmployeesQuery> for EmployeesQueryHandler {
+    type ReadModelStore = ReadModelStoreImpl;
+    type Output = Page<EmployeeReadModel>;
+    async fn handle(
+        read_model_store: ReadModelStoreRef<'_, ReadModelStoreImpl>,
+        query: EmployeesQuery,
+    ) -> Result<Page<EmployeeReadModel>> {
+        read_model_store.list(query.cursor, query.limit).await
+    }
+}
+
+struct EmployeesByAddressQuery {
+    prefix: String,
+    cursor: Option<u64>,
+    limit: usize,
+}
+
+impl Query for EmployeesByAddressQuery {
+    type Handler = EmployeesByAddressQueryHandler;
+}
+
+struct EmployeesByAddressQueryHandler;
+
+impl QueryHandler<EmployeesByAddressQuery> for EmployeesByAddressQueryHandler {
+    type ReadModelStore = ReadModelStoreImpl;
+    type Output = Page<EmployeeReadModel>;
+    async fn handle(
+        read_model_store: ReadModelStoreRef<'_, ReadModelStoreImpl>,
+        query: EmployeesByAddressQuery,
+    ) -> Result<Page<EmployeeReadModel>> {
+        read_model_store
+            .scan(query.cursor, query.limit, |x| {
+                x.address.starts_with(&query.prefix)
+            })
+            .await
+    }
+}
+
 #[derive(Default)]
 struct ReadModelStoreImpl {
-    employees: Mutex<HashMap<u64, EmployeeReadModel>>,
+    employees: Mutex<BTreeMap<u64, (u32, Option<EmployeeReadModel>)>>,
 }
 
 impl ReadModelStore for ReadModelStoreImpl {
     type ReadModel = EmployeeReadModel;
 
     async fn read(&self, id: u64) -> Result<Option<Self::ReadModel>> {
-        Ok(self.employees.lock().unwrap().get(&id).cloned())
+        Ok(self
+            .employees
+            .lock()
+            .unwrap()
+            .get(&id)
+            .and_then(|(_, x)| x.clone()))
     }
 
-    async fn save(&self, id: u64, read_model: &Self::ReadModel) -> Result<()> {
+    async fn save(&self, id: u64, version: u32, read_model: &Self::ReadModel) -> Result<()> {
         self.employees
             .lock()
             .unwrap()
-            .insert(id, read_model.clone());
+            .insert(id, (version, Some(read_model.clone())));
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: u64, version: u32) -> Result<()> {
+        self.employees.lock().unwrap().insert(id, (version, None));
+
+        Ok(())
+    }
+
+    async fn position(&self, id: u64) -> Result<u32> {
+        Ok(self
+            .employees
+            .lock()
+            .unwrap()
+            .get(&id)
+            .map_or(0, |(version, _)| *version))
+    }
+}
+
+#[derive(Default)]
+struct NameHistoryStoreImpl {
+    name_histories: Mutex<HashMap<u64, (u32, Option<NameHistoryReadModel>)>>,
+}
+
+impl ReadModelStore for NameHistoryStoreImpl {
+    type ReadModel = NameHistoryReadModel;
+
+    async fn read(&self, id: u64) -> Result<Option<Self::ReadModel>> {
+        Ok(self
+            .name_histories
+            .lock()
+            .unwrap()
+            .get(&id)
+            .and_then(|(_, x)| x.clone()))
+    }
+
+    async fn save(&self, id: u64, version: u32, read_model: &Self::ReadModel) -> Result<()> {
+        self.name_histories
+            .lock()
+            .unwrap()
+            .insert(id, (version, Some(read_model.clone())));
 
         Ok(())
     }
+
+    async fn delete(&self, id: u64, version: u32) -> Result<()> {
+        self.name_histories
+            .lock()
+            .unwrap()
+            .insert(id, (version, None));
+
+        Ok(())
+    }
+
+    async fn position(&self, id: u64) -> Result<u32> {
+        Ok(self
+            .name_histories
+            .lock()
+            .unwrap()
+            .get(&id)
+            .map_or(0, |(version, _)| *version))
+    }
+}
+
+impl ReadModelScan for ReadModelStoreImpl {
+    async fn scan<P>(
+        &self,
+        cursor: Option<u64>,
+        limit: usize,
+        predicate: P,
+    ) -> Result<Page<EmployeeReadModel>>
+    where
+        P: Fn(&EmployeeReadModel) -> bool + Send,
+    {
+        if limit == 0 {
+            return Err(FrameworkError::InvalidScanLimit);
+        }
+
+        let start = cursor.map_or(Bound::Unbounded, Bound::Excluded);
+
+        let mut items = self
+            .employees
+            .lock()
+            .unwrap()
+            .range((start, Bound::Unbounded))
+            .filter_map(|(id, (_, x))| x.as_ref().map(|x| (id, x)))
+            .filter(|(_, x)| predicate(x))
+            .take(limit + 1)
+            .map(|(id, x)| (*id, x.clone()))
+            .collect::<Vec<_>>();
+
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|(id, _)| *id)
+        } else {
+            None
+        };
+
+        Ok(Page { items, next_cursor })
+    }
 }
 
+// toy cipher for demonstration, not suitable for real data
 #[derive(Default)]
-struct EventStoreImpl {
-    events: Mutex<HashMap<u64, Vec<Value>>>,
+struct XorCipher {
+    keys: Mutex<HashMap<(AggregateTypeId, u64), Option<u8>>>,
 }
 
-impl EventStore for EventStoreImpl {
-    async fn read<A>(&self, aggregate_id: u64, from_version: u32) -> Result<Vec<A::Event>>
+impl Cipher for XorCipher {
+    async fn encrypt<A>(&self, aggregate_id: u64, data: Vec<u8>) -> Result<Vec<u8>>
+    where
+        A: Aggregate,
+    {
+        let key = *self
+            .keys
+            .lock()
+            .unwrap()
+            .entry((A::type_id(), aggregate_id))
+            .or_insert(Some(aggregate_id as u8 ^ 0x5a));
+
+        let Some(key) = key else {
+            return Err(FrameworkError::AggregateShredded(aggregate_id));
+        };
+
+        Ok(data.into_iter().map(|x| x ^ key).collect())
+    }
+
+    async fn decrypt<A>(&self, aggregate_id: u64, data: Vec<u8>) -> Result<Vec<u8>>
+    where
+        A: Aggregate,
+    {
+        let key = self
+            .keys
+            .lock()
+            .unwrap()
+            .get(&(A::type_id(), aggregate_id))
+            .copied()
+            .flatten();
+
+        let Some(key) = key else {
+            return Err(FrameworkError::AggregateShredded(aggregate_id));
+        };
+
+        Ok(data.into_iter().map(|x| x ^ key).collect())
+    }
+
+    async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
     where
         A: Aggregate,
     {
+        self.keys
+            .lock()
+            .unwrap()
+            .insert((A::type_id(), aggregate_id), None);
+
+        Ok(())
+    }
+}
+
+type EventStream = Vec<(u32, CodecId, Vec<u8>)>;
+
+struct EventStoreImpl<C, D>
+where
+    C: Cipher,
+    D: Codec,
+{
+    events: Mutex<HashMap<u64, EventStream>>,
+    tombstones: Mutex<HashSet<u64>>,
+    cipher: Arc<C>,
+    _codec: PhantomData<D>,
+}
+
+impl<C, D> EventStoreImpl<C, D>
+where
+    C: Cipher,
+    D: Codec,
+{
+    fn new(cipher: Arc<C>) -> Self {
+        Self {
+            events: Mutex::new(HashMap::new()),
+            tombstones: Mutex::new(HashSet::new()),
+            cipher,
+            _codec: PhantomData,
+        }
+    }
+}
+
+impl<C, D> EventStore for EventStoreImpl<C, D>
+where
+    C: Cipher,
+    D: Codec + Sync + Send,
+{
+    async fn read<A>(
+        &self,
+        aggregate_id: u64,
+        from_version: u32,
+    ) -> Result<Vec<VersionedEvent<A::Event>>>
+    where
+        A: Aggregate,
+    {
+        if self.tombstones.lock().unwrap().contains(&aggregate_id) {
+            return Err(FrameworkError::AggregateDeleted(aggregate_id));
+        }
+
         let events = self
             .events
             .lock()
@@ -241,37 +517,210 @@ impl EventStore for EventStoreImpl {
             .cloned()
             .unwrap_or_default();
 
-        Ok(events
-            .iter()
-            .map(|x| serde_json::from_value::<A::Event>(x.clone()).unwrap())
-            .filter(|x| x.version() > from_version)
-            .collect())
+        let mut result = Vec::with_capacity(events.len());
+        for (version, codec_id, x) in events {
+            if version <= from_version {
+                continue;
+            }
+
+            let data = self.cipher.decrypt::<A>(aggregate_id, x).await?;
+            result.push(VersionedEvent {
+                version,
+                event: D::decode_tagged(codec_id, &data)?,
+            });
+        }
+
+        Ok(result)
+    }
+
+    async fn save<A>(
+        &self,
+        aggregate_id: u64,
+        expected_version: u32,
+        events: &[A::Event],
+    ) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        if self.tombstones.lock().unwrap().contains(&aggregate_id) {
+            return Err(FrameworkError::AggregateDeleted(aggregate_id));
+        }
+
+        let mut encrypted = Vec::with_capacity(events.len());
+        for event in events {
+            let data = D::encode(event)?;
+            encrypted.push(self.cipher.encrypt::<A>(aggregate_id, data).await?);
+        }
+
+        let mut streams = self.events.lock().unwrap();
+        let stream = streams.entry(aggregate_id).or_default();
+
+        let version = stream.last().map(|(version, _, _)| *version).unwrap_or(0);
+        if version != expected_version {
+            return Err(FrameworkError::ConcurrencyError);
+        }
+
+        stream.extend(
+            (expected_version + 1..)
+                .zip(encrypted)
+                .map(|(version, x)| (version, D::ID, x)),
+        );
+
+        Ok(())
+    }
+
+    async fn tombstone<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.tombstones.lock().unwrap().insert(aggregate_id);
+        self.events.lock().unwrap().remove(&aggregate_id);
+
+        Ok(())
     }
 
-    async fn save<A>(&self, aggregate_id: u64, events: &[A::Event]) -> Result<()>
+    async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
     where
         A: Aggregate,
     {
-        self.events
+        self.cipher.shred::<A>(aggregate_id).await
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct OnboardingSaga {
+    employee_id: u64,
+    deadline: Option<u64>,
+}
+
+impl Saga for OnboardingSaga {
+    type Aggregate = EmployeeAggregate;
+    type Command = EmployeeCommand;
+
+    fn type_id() -> SagaTypeId
+    where
+        Self: Sized,
+    {
+        1
+    }
+
+    fn correlation_id(aggregate_id: u64, _event: &EmployeeEvent) -> Option<u64> {
+        Some(aggregate_id)
+    }
+
+    fn handle(&mut self, event: &EmployeeEvent) -> Result<Vec<EmployeeCommand>> {
+        match event {
+            EmployeeEvent::EmployeeCreated { id, address, .. } => {
+                self.employee_id = *id;
+                if address.is_empty() {
+                    self.deadline = Some(10);
+                }
+            }
+            EmployeeEvent::AddressChanged { .. } | EmployeeEvent::EmployeeDeleted => {
+                self.deadline = None;
+            }
+            EmployeeEvent::NameChanged { .. } => {}
+        }
+
+        Ok(Vec::new())
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        self.deadline
+    }
+
+    fn handle_timeout(&mut self) -> Result<Vec<EmployeeCommand>> {
+        self.deadline = None;
+
+        Ok(vec![EmployeeCommand::ChangeAddress {
+            id: self.employee_id,
+            address: "unassigned".into(),
+        }])
+    }
+}
+
+#[derive(Default)]
+struct SagaStoreImpl {
+    sagas: Mutex<HashMap<(SagaTypeId, u64), (u32, Value)>>,
+}
+
+impl SagaStore for SagaStoreImpl {
+    async fn read<S>(&self, correlation_id: u64) -> Result<Option<(u32, S)>>
+    where
+        S: Saga,
+    {
+        let saga = self
+            .sagas
             .lock()
             .unwrap()
-            .entry(aggregate_id)
-            .or_default()
-            .extend(events.iter().map(|x| serde_json::to_value(x).unwrap()));
+            .get(&(S::type_id(), correlation_id))
+            .cloned();
+
+        Ok(saga.map(|(version, x)| (version, serde_json::from_value(x).unwrap())))
+    }
+
+    async fn save<S>(&self, correlation_id: u64, expected_version: u32, saga: &S) -> Result<()>
+    where
+        S: Saga,
+    {
+        let mut sagas = self.sagas.lock().unwrap();
+
+        let key = (S::type_id(), correlation_id);
+        let version = sagas.get(&key).map_or(0, |(version, _)| *version);
+        if version != expected_version {
+            return Err(FrameworkError::ConcurrencyError);
+        }
+
+        sagas.insert(key, (version + 1, serde_json::to_value(saga).unwrap()));
 
         Ok(())
     }
+
+    async fn expired<S>(&self, now: u64) -> Result<Vec<u64>>
+    where
+        S: Saga,
+    {
+        Ok(self
+            .sagas
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((type_id, _), _)| *type_id == S::type_id())
+            .filter(|(_, (_, x))| {
+                serde_json::from_value::<S>(x.clone())
+                    .unwrap()
+                    .deadline()
+                    .is_some_and(|deadline| deadline <= now)
+            })
+            .map(|((_, correlation_id), _)| *correlation_id)
+            .collect())
+    }
 }
 
 #[tokio::main]
-pub async fn main() -> Result<()> {
+pub async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cipher = Arc::new(XorCipher::default());
+
     let mut framework = Framework::new(
-        EventStoreImpl::default(),
+        EventStoreImpl::<_, JsonCodec>::new(cipher.clone()),
         DummySnapshotStore,
-        (ReadModelStoreImpl::default(),),
+        (
+            ReadModelStoreImpl::default(),
+            NameHistoryStoreImpl::default(),
+        ),
     );
 
-    framework.register_event_callback(1, |x| {
+    framework.register_saga::<OnboardingSaga, _>(SagaStoreImpl::default());
+
+    let mut command_bus = CommandBus::<_, _, _, JsonCodec>::new();
+    command_bus.register::<EmployeeCommand>("employee");
+
+    let mut query_bus = QueryBus::<_, _, _, JsonCodec>::new();
+    query_bus.register::<EmployeeQuery>("employee");
+
+    framework.register_aggregate::<EmployeeAggregate>()?;
+
+    framework.register_event_callback::<EmployeeAggregate, _>(1, |x| {
         println!(
             "EmployeeCreated: {:?}",
             x.as_any().downcast_ref::<EmployeeEvent>()
@@ -291,14 +740,17 @@ pub async fn main() -> Result<()> {
     let employee = framework.query(EmployeeQuery { id: 1 }).await?.unwrap();
     println!("{:?}", employee);
 
-    framework
+    let position = framework
         .command(EmployeeCommand::ChangeName {
             id: 1,
             name: "new name".into(),
         })
         .await?;
 
-    let employee = framework.query(EmployeeQuery { id: 1 }).await?.unwrap();
+    let employee = framework
+        .query_at_least(EmployeeQuery { id: 1 }, Some(position))
+        .await?
+        .unwrap();
     println!("{:?}", employee);
 
     framework
@@ -311,5 +763,104 @@ pub async fn main() -> Result<()> {
     let employee = framework.query(EmployeeQuery { id: 1 }).await?.unwrap();
     println!("{:?}", employee);
 
+    let result = framework
+        .command(EmployeeCommand::ChangeName {
+            id: 1,
+            name: "".into(),
+        })
+        .await;
+    println!("{:?}", result);
+
+    let result = framework
+        .command(EmployeeCommand::ChangeName {
+            id: 2,
+            name: "missing".into(),
+        })
+        .await;
+    println!("{:?}", result);
+
+    framework
+        .command(EmployeeCommand::CreateEmployee {
+            id: 3,
+            name: "onboarding".into(),
+            address: "".into(),
+        })
+        .await?;
+
+    framework.handle_saga_timeouts(10).await;
+
+    let employee = framework.query(EmployeeQuery { id: 3 }).await?.unwrap();
+    println!("{:?}", employee);
+
+    command_bus
+        .dispatch(
+            &framework,
+            "employee",
+            br#"{"ChangeName": {"id": 3, "name": "from bus"}}"#,
+        )
+        .await?;
+
+    let employee = query_bus
+        .dispatch(&framework, "employee", br#"{"id": 3}"#)
+        .await?;
+    println!("{}", String::from_utf8(employee)?);
+
+    let employees = framework
+        .query(EmployeesQuery {
+            cursor: None,
+            limit: 1,
+        })
+        .await?;
+    println!("{:?}", employees);
+
+    let employees = framework
+        .query(EmployeesQuery {
+            cursor: employees.next_cursor,
+            limit: 1,
+        })
+        .await?;
+    println!("{:?}", employees);
+
+    let employees = framework
+        .query(EmployeesByAddressQuery {
+            prefix: "new".into(),
+            cursor: None,
+            limit: 10,
+        })
+        .await?;
+    println!("{:?}", employees);
+
+    let details = framework.query(EmployeeDetailsQuery { id: 1 }).await?;
+    println!("{:?}", details);
+
+    let position = framework
+        .command(EmployeeCommand::DeleteEmployee { id: 1 })
+        .await?;
+
+    let details = framework
+        .query_at_least(EmployeeDetailsQuery { id: 1 }, Some(position))
+        .await?;
+    println!("{:?}", details);
+
+    framework.delete::<EmployeeAggregate>(1).await?;
+
+    let result = framework
+        .command(EmployeeCommand::ChangeName {
+            id: 1,
+            name: "deleted".into(),
+        })
+        .await;
+    println!("{:?}", result);
+
+    framework.shred::<EmployeeAggregate>(3).await?;
+
+    let result = framework
+        .command(EmployeeCommand::ChangeName {
+            id: 3,
+            name: "shredded".into(),
+        })
+        .await;
+    println!("{:?}", result);
+
     Ok(())
 }
@@ -1,4 +1,5 @@
 use alloc::vec::Vec;
+use core::error::Error;
 
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -9,11 +10,15 @@ pub type AggregateTypeId = u32;
 pub trait Aggregate: Sync + Send + Default + Serialize + DeserializeOwned {
     type Command: Command;
     type Event: Event + Serialize + DeserializeOwned;
+    type Error: Error + Sync + Send + 'static;
 
     fn type_id() -> AggregateTypeId
     where
         Self: Sized;
-    fn version(&self) -> u32;
-    fn handle(&self, command: Self::Command) -> Result<Vec<Self::Event>>;
+    fn type_name() -> &'static str
+    where
+        Self: Sized;
+    fn handle(&self, command: Self::Command)
+        -> core::result::Result<Vec<Self::Event>, Self::Error>;
     fn apply_events(&mut self, events: Vec<Self::Event>) -> Result<()>;
 }
@@ -0,0 +1,246 @@
+use alloc::{boxed::Box, collections::BTreeMap};
+use core::any::Any;
+use std::sync::{Mutex, PoisonError};
+
+use crate::aggregate::{Aggregate, AggregateTypeId};
+
+type Key = (AggregateTypeId, u64);
+
+struct Entry {
+    used: u64,
+    version: u32,
+    aggregate: Box<dyn Any + Send + Sync>,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: BTreeMap<Key, Entry>,
+    // keys by last use, oldest first
+    lru: BTreeMap<u64, Key>,
+    next_use: u64,
+}
+
+// Aggregates kept between commands, up to `capacity`, evicting the least recently used. An entry
+// is taken out while a command works on it and put back afterwards, entries are brought up to
+// date with the store on the way out so they're never trusted blindly.
+pub(crate) struct AggregateCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl AggregateCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    pub(crate) fn take<A>(&self, aggregate_id: u64) -> Option<(u32, A)>
+    where
+        A: Aggregate + 'static,
+    {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let entry = entries.entries.remove(&(A::type_id(), aggregate_id))?;
+        entries.lru.remove(&entry.used);
+
+        let aggregate = entry.aggregate.downcast::<A>().ok()?;
+        Some((entry.version, *aggregate))
+    }
+
+    pub(crate) fn put<A>(&self, aggregate_id: u64, version: u32, aggregate: A)
+    where
+        A: Aggregate + 'static,
+    {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let key = (A::type_id(), aggregate_id);
+        let used = entries.next_use;
+        entries.next_use += 1;
+
+        // a concurrent command may have put back an older version
+        if let Some(entry) = entries.entries.get(&key) {
+            if entry.version > version {
+                return;
+            }
+            let previous = entry.used;
+            entries.lru.remove(&previous);
+        }
+
+        entries.entries.insert(
+            key,
+            Entry {
+                used,
+                version,
+                aggregate: Box::new(aggregate),
+            },
+        );
+        entries.lru.insert(used, key);
+
+        while entries.entries.len() > self.capacity {
+            let Some((_, oldest)) = entries.lru.pop_first() else {
+                break;
+            };
+            entries.entries.remove(&oldest);
+        }
+    }
+
+    pub(crate) fn remove<A>(&self, aggregate_id: u64)
+    where
+        A: Aggregate,
+    {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(entry) = entries.entries.remove(&(A::type_id(), aggregate_id)) {
+            entries.lru.remove(&entry.used);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::TestAggregate;
+
+    fn aggregate(total: u64) -> TestAggregate {
+        TestAggregate {
+            total,
+            ..Default::default()
+        }
+    }
+
+    fn take(cache: &AggregateCache, aggregate_id: u64) -> Option<(u32, u64)> {
+        cache
+            .take::<TestAggregate>(aggregate_id)
+            .map(|(version, aggregate)| (version, aggregate.total))
+    }
+
+    #[test]
+    fn entries_are_taken_once() {
+        let cache = AggregateCache::new(2);
+        assert_eq!(take(&cache, 1), None);
+
+        cache.put(1, 3, aggregate(5));
+        assert_eq!(take(&cache, 1), Some((3, 5)));
+        assert_eq!(take(&cache, 1), None, "a command has it");
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let cache = AggregateCache::new(2);
+        cache.put(1, 1, aggregate(1));
+        cache.put(2, 1, aggregate(2));
+
+        // 1 is used again, 2 becomes the oldest
+        let (version, used) = cache.take::<TestAggregate>(1).unwrap();
+        cache.put(1, version, used);
+        cache.put(3, 1, aggregate(3));
+
+        assert_eq!(take(&cache, 2), None);
+        assert_eq!(take(&cache, 1), Some((1, 1)));
+        assert_eq!(take(&cache, 3), Some((1, 3)));
+    }
+
+    #[test]
+    fn older_versions_are_not_put_back() {
+        let cache = AggregateCache::new(2);
+        cache.put(1, 3, aggregate(3));
+        cache.put(1, 2, aggregate(2));
+        assert_eq!(take(&cache, 1), Some((3, 3)));
+
+        cache.put(1, 3, aggregate(3));
+        cache.put(1, 4, aggregate(4));
+        assert_eq!(take(&cache, 1), Some((4, 4)));
+    }
+
+    #[test]
+    fn nothing_is_kept_without_capacity() {
+        let cache = AggregateCache::new(0);
+        cache.put(1, 1, aggregate(1));
+        assert_eq!(take(&cache, 1), None);
+    }
+
+    #[test]
+    fn removed_entries_are_gone() {
+        let cache = AggregateCache::new(2);
+        cache.put(1, 1, aggregate(1));
+        cache.remove::<TestAggregate>(1);
+        assert_eq!(take(&cache, 1), None);
+
+        // the slot is free again
+        cache.put(2, 1, aggregate(2));
+        cache.put(3, 1, aggregate(3));
+        assert_eq!(take(&cache, 2), Some((1, 2)));
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "json"))]
+    mod repository {
+        use alloc::string::String;
+
+        use super::*;
+        use crate::{
+            codec::JsonCodec, error::FrameworkError, event::EventStore,
+            repository::AggregateRepository, snapshot::DummySnapshotStore, sqlite::SqliteDatabase,
+            testing::TestEvent,
+        };
+
+        #[tokio::test]
+        async fn stale_entry_catches_up_with_the_store() {
+            let event_store = SqliteDatabase::open_in_memory()
+                .unwrap()
+                .event_store::<JsonCodec>();
+            let cache = AggregateCache::new(2);
+            let repository =
+                AggregateRepository::<TestAggregate, _, _>::new(&event_store, &DummySnapshotStore)
+                    .with_cache(Some(&cache));
+
+            event_store
+                .save::<TestAggregate>(1, 0, &[TestEvent::Added(1)])
+                .await
+                .unwrap();
+            cache.put(1, 1, aggregate(1));
+
+            // appended by another writer, after the aggregate was cached
+            event_store
+                .save::<TestAggregate>(1, 1, &[TestEvent::Renamed(String::from("a"))])
+                .await
+                .unwrap();
+
+            let (version, aggregate) = repository.read(1).await.unwrap().unwrap();
+            assert_eq!(version, 2);
+            assert_eq!((aggregate.total, aggregate.name.as_str()), (1, "a"));
+        }
+
+        #[tokio::test]
+        async fn evict_drops_the_entry_after_a_failed_save() {
+            let event_store = SqliteDatabase::open_in_memory()
+                .unwrap()
+                .event_store::<JsonCodec>();
+            let cache = AggregateCache::new(2);
+            let repository =
+                AggregateRepository::<TestAggregate, _, _>::new(&event_store, &DummySnapshotStore)
+                    .with_cache(Some(&cache));
+
+            event_store
+                .save::<TestAggregate>(1, 0, &[TestEvent::Added(1), TestEvent::Added(1)])
+                .await
+                .unwrap();
+            cache.put(1, 1, aggregate(1));
+
+            assert!(matches!(
+                repository
+                    .save(1, 1, aggregate(1), &[TestEvent::Added(1)])
+                    .await,
+                Err(FrameworkError::ConcurrencyError)
+            ));
+
+            repository.evict(1);
+            assert_eq!(take(&cache, 1), None);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::future::Future;
+
+use crate::{aggregate::Aggregate, Result};
+
+// encryption-at-rest hook for stores, keyed per aggregate so that destroying
+// a key with `shred` makes the aggregate's payloads unreadable
+pub trait Cipher: Sync + Send {
+    fn encrypt<A>(
+        &self,
+        aggregate_id: u64,
+        data: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send
+    where
+        A: Aggregate;
+    // fails with `AggregateShredded` once the aggregate's key is destroyed
+    fn decrypt<A>(
+        &self,
+        aggregate_id: u64,
+        data: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send
+    where
+        A: Aggregate;
+    fn shred<A>(&self, aggregate_id: u64) -> impl Future<Output = Result<()>> + Send
+    where
+        A: Aggregate;
+}
+
+pub struct NoCipher;
+
+impl Cipher for NoCipher {
+    async fn encrypt<A>(&self, _aggregate_id: u64, data: Vec<u8>) -> Result<Vec<u8>>
+    where
+        A: Aggregate,
+    {
+        Ok(data)
+    }
+
+    async fn decrypt<A>(&self, _aggregate_id: u64, data: Vec<u8>) -> Result<Vec<u8>>
+    where
+        A: Aggregate,
+    {
+        Ok(data)
+    }
+
+    async fn shred<A>(&self, _aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        Ok(())
+    }
+}
+
+// lets stores share the cipher
+impl<K> Cipher for Arc<K>
+where
+    K: Cipher,
+{
+    fn encrypt<A>(
+        &self,
+        aggregate_id: u64,
+        data: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send
+    where
+        A: Aggregate,
+    {
+        self.as_ref().encrypt::<A>(aggregate_id, data)
+    }
+
+    fn decrypt<A>(
+        &self,
+        aggregate_id: u64,
+        data: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send
+    where
+        A: Aggregate,
+    {
+        self.as_ref().decrypt::<A>(aggregate_id, data)
+    }
+
+    fn shred<A>(&self, aggregate_id: u64) -> impl Future<Output = Result<()>> + Send
+    where
+        A: Aggregate,
+    {
+        self.as_ref().shred::<A>(aggregate_id)
+    }
+}
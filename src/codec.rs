@@ -0,0 +1,233 @@
+#[cfg(any(feature = "json", feature = "postcard", feature = "cbor"))]
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{error::FrameworkError, Result};
+
+pub type CodecId = u8;
+
+pub trait Codec {
+    // tag stored alongside encoded records
+    const ID: CodecId;
+
+    fn encode<T>(value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize;
+    fn decode<T>(data: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned;
+
+    fn decode_tagged<T>(codec_id: CodecId, data: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        if codec_id == Self::ID {
+            Self::decode(data)
+        } else {
+            Err(FrameworkError::UnknownCodec(codec_id))
+        }
+    }
+}
+
+// encodes with `C1`, decodes records written by either codec, for migrating streams between formats
+impl<C1, C2> Codec for (C1, C2)
+where
+    C1: Codec,
+    C2: Codec,
+{
+    const ID: CodecId = C1::ID;
+
+    fn encode<T>(value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        C1::encode(value)
+    }
+
+    fn decode<T>(data: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        C1::decode(data)
+    }
+
+    fn decode_tagged<T>(codec_id: CodecId, data: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        if codec_id == C1::ID {
+            C1::decode(data)
+        } else {
+            C2::decode_tagged(codec_id, data)
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl Codec for JsonCodec {
+    const ID: CodecId = 1;
+
+    fn encode<T>(value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        serde_json::to_vec(value).map_err(|e| FrameworkError::SerializationError(e.to_string()))
+    }
+
+    fn decode<T>(data: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(data).map_err(|e| FrameworkError::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(feature = "postcard")]
+pub struct PostcardCodec;
+
+#[cfg(feature = "postcard")]
+impl Codec for PostcardCodec {
+    const ID: CodecId = 2;
+
+    fn encode<T>(value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        postcard::to_allocvec(value).map_err(|e| FrameworkError::SerializationError(e.to_string()))
+    }
+
+    fn decode<T>(data: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        postcard::from_bytes(data).map_err(|e| FrameworkError::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(feature = "cbor")]
+pub struct CborCodec;
+
+#[cfg(feature = "cbor")]
+impl Codec for CborCodec {
+    const ID: CodecId = 3;
+
+    fn encode<T>(value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        let mut data = Vec::new();
+        ciborium::into_writer(value, &mut data)
+            .map_err(|e| FrameworkError::SerializationError(e.to_string()))?;
+
+        Ok(data)
+    }
+
+    fn decode<T>(data: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        ciborium::from_reader(data).map_err(|e| FrameworkError::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "testing",
+    any(feature = "json", feature = "postcard", feature = "cbor")
+))]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::testing::{TestAggregate, TestEvent};
+
+    fn aggregate() -> TestAggregate {
+        TestAggregate {
+            total: 300,
+            name: String::from("a"),
+            removed: true,
+        }
+    }
+
+    fn round_trip<C>()
+    where
+        C: Codec,
+    {
+        let data = C::encode(&aggregate()).unwrap();
+        assert_eq!(C::decode::<TestAggregate>(&data).unwrap(), aggregate());
+
+        let data = C::encode(&TestEvent::Renamed(String::from("b"))).unwrap();
+        assert_eq!(
+            C::decode_tagged::<TestEvent>(C::ID, &data).unwrap(),
+            TestEvent::Renamed(String::from("b"))
+        );
+        assert!(matches!(
+            C::decode_tagged::<TestEvent>(C::ID + 1, &data),
+            Err(FrameworkError::UnknownCodec(_))
+        ));
+        assert!(matches!(
+            C::decode::<TestEvent>(&data[..data.len() - 1]),
+            Err(FrameworkError::SerializationError(_))
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        round_trip::<JsonCodec>();
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn postcard_round_trip() {
+        round_trip::<PostcardCodec>();
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trip() {
+        round_trip::<CborCodec>();
+    }
+
+    #[cfg(all(feature = "json", feature = "postcard"))]
+    #[test]
+    fn migration_decodes_either_codec_and_encodes_with_the_first() {
+        type Migrating = (PostcardCodec, JsonCodec);
+        assert_eq!(Migrating::ID, PostcardCodec::ID);
+
+        let old = JsonCodec::encode(&aggregate()).unwrap();
+        let aggregate = Migrating::decode_tagged::<TestAggregate>(JsonCodec::ID, &old).unwrap();
+
+        let new = Migrating::encode(&aggregate).unwrap();
+        assert_eq!(
+            PostcardCodec::decode::<TestAggregate>(&new).unwrap(),
+            aggregate
+        );
+        assert_eq!(
+            Migrating::decode_tagged::<TestAggregate>(PostcardCodec::ID, &new).unwrap(),
+            aggregate
+        );
+    }
+
+    #[cfg(all(feature = "json", feature = "postcard"))]
+    #[test]
+    fn migration_refuses_other_codecs_and_bad_data() {
+        type Migrating = (PostcardCodec, JsonCodec);
+
+        let data = JsonCodec::encode(&aggregate()).unwrap();
+        assert!(matches!(
+            Migrating::decode_tagged::<TestAggregate>(4, &data),
+            Err(FrameworkError::UnknownCodec(4))
+        ));
+        // tagged as the old codec, but not valid in it
+        assert!(matches!(
+            Migrating::decode_tagged::<TestAggregate>(JsonCodec::ID, b"{"),
+            Err(FrameworkError::SerializationError(_))
+        ));
+    }
+}
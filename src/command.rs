@@ -1,7 +1,25 @@
-use crate::Aggregate;
+use crate::{aggregate::AggregateTypeId, Aggregate};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpectedState {
+    Any,
+    Exists,
+    NotExists,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub aggregate_type_id: AggregateTypeId,
+    pub aggregate_id: u64,
+    pub version: u32,
+}
 
 pub trait Command {
     type Aggregate: Aggregate<Command = Self> + 'static;
 
     fn aggregate_id(&self) -> u64;
+
+    fn expected_state(&self) -> ExpectedState {
+        ExpectedState::Any
+    }
 }
@@ -0,0 +1,183 @@
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+use core::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    codec::Codec,
+    command::{Command, Position},
+    error::FrameworkError,
+    event::EventStore,
+    framework::Framework,
+    read_model::ReadModelStores,
+    snapshot::SnapshotStore,
+    BoxFuture, Result,
+};
+
+type BoxedCommandHandler<F> =
+    Box<dyn for<'a> Fn(&'a F, &'a [u8]) -> BoxFuture<'a, Result<Position>> + Sync + Send>;
+
+pub struct CommandBus<E, S, R, C>
+where
+    E: EventStore + 'static,
+    S: SnapshotStore + 'static,
+    R: ReadModelStores,
+    C: Codec,
+{
+    handlers: BTreeMap<String, BoxedCommandHandler<Framework<E, S, R>>>,
+    _phantom: PhantomData<C>,
+}
+
+impl<E, S, R, C> CommandBus<E, S, R, C>
+where
+    E: EventStore + Sync + 'static,
+    S: SnapshotStore + Sync + 'static,
+    R: ReadModelStores + Sync,
+    C: Codec,
+{
+    pub fn new() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn register<M>(&mut self, name: &str)
+    where
+        M: Command + DeserializeOwned + Send + 'static,
+    {
+        self.handlers.insert(
+            name.to_string(),
+            Box::new(|framework, payload| {
+                Box::pin(async move {
+                    let command = C::decode::<M>(payload)?;
+
+                    framework.dispatch(command).await
+                })
+            }),
+        );
+    }
+
+    pub async fn dispatch(
+        &self,
+        framework: &Framework<E, S, R>,
+        name: &str,
+        payload: &[u8],
+    ) -> Result<Position> {
+        let Some(handler) = self.handlers.get(name) else {
+            return Err(FrameworkError::NoSuchCommand(name.to_string()));
+        };
+
+        handler(framework, payload).await
+    }
+}
+
+impl<E, S, R, C> Default for CommandBus<E, S, R, C>
+where
+    E: EventStore + Sync + 'static,
+    S: SnapshotStore + Sync + 'static,
+    R: ReadModelStores + Sync,
+    C: Codec,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "sqlite", feature = "json"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{
+        codec::JsonCodec,
+        error::CommandError,
+        snapshot::DummySnapshotStore,
+        sqlite::{SqliteDatabase, SqliteEventStore},
+        testing::{TestCommand, TestError, TestEvent},
+    };
+
+    type TestBus = CommandBus<SqliteEventStore<JsonCodec>, DummySnapshotStore, (), JsonCodec>;
+
+    fn setup() -> (
+        Framework<SqliteEventStore<JsonCodec>, DummySnapshotStore, ()>,
+        TestBus,
+    ) {
+        let event_store = SqliteDatabase::open_in_memory()
+            .unwrap()
+            .event_store::<JsonCodec>();
+        let framework = Framework::new(event_store, DummySnapshotStore, ());
+
+        let mut bus = TestBus::new();
+        bus.register::<TestCommand>("test");
+
+        (framework, bus)
+    }
+
+    fn payload(aggregate_id: u64, event: TestEvent) -> Vec<u8> {
+        JsonCodec::encode(&TestCommand {
+            aggregate_id,
+            event,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn dispatches_decoded_commands() {
+        let (framework, bus) = setup();
+
+        let position = bus
+            .dispatch(&framework, "test", &payload(1, TestEvent::Added(2)))
+            .await
+            .unwrap();
+        assert_eq!((position.aggregate_id, position.version), (1, 1));
+
+        let position = bus
+            .dispatch(&framework, "test", &payload(1, TestEvent::Removed))
+            .await
+            .unwrap();
+        assert_eq!(position.version, 2);
+    }
+
+    #[tokio::test]
+    async fn unknown_names_are_refused() {
+        let (framework, bus) = setup();
+
+        assert!(matches!(
+            bus.dispatch(&framework, "other", &payload(1, TestEvent::Added(2)))
+                .await,
+            Err(FrameworkError::NoSuchCommand(name)) if name == "other"
+        ));
+    }
+
+    #[tokio::test]
+    async fn undecodable_payloads_are_refused() {
+        let (framework, bus) = setup();
+
+        assert!(matches!(
+            bus.dispatch(&framework, "test", b"{\"aggregate_id\": 1}")
+                .await,
+            Err(FrameworkError::SerializationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejections_surface_as_framework_errors() {
+        let (framework, bus) = setup();
+
+        let error = bus
+            .dispatch(&framework, "test", &payload(1, TestEvent::Removed))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, FrameworkError::CommandRejected(_)));
+        assert_eq!(error.to_string(), "Command rejected: Nothing to remove");
+        assert_eq!(
+            CommandError::Domain::<TestError>(TestError).to_string(),
+            "Nothing to remove"
+        );
+    }
+}
@@ -9,8 +9,43 @@ pub enum FrameworkError {
     SerializationError(String),
     #[error("Invalid event version, {0} expected, got {1}")]
     InvalidEventVersion(u32, u32),
+    #[error("Unknown codec {0}")]
+    UnknownCodec(u8),
     #[error("Concurrency error")]
     ConcurrencyError,
+    #[error("Aggregate {0} not found")]
+    AggregateNotFound(u64),
+    #[error("Aggregate {0} already exists")]
+    AggregateAlreadyExists(u64),
+    #[error("Aggregate {0} is deleted")]
+    AggregateDeleted(u64),
+    #[error("Aggregate {0} is shredded")]
+    AggregateShredded(u64),
+    #[error("Command rejected: {0}")]
+    CommandRejected(String),
+    #[error("No such command: {0}")]
+    NoSuchCommand(String),
     #[error("Invalid query")]
     NoSuchReadModelStore,
+    #[error("No such query: {0}")]
+    NoSuchQuery(String),
+    #[error("Read model is behind, version {0} expected, got {1}")]
+    ReadModelBehind(u32, u32),
+    #[error("Aggregate type id {0} is used by more than one aggregate")]
+    DuplicateAggregateTypeId(u32),
+    #[error("Event type id {1} is used more than once by aggregate type {0}")]
+    DuplicateEventTypeId(u32, u32),
+    #[error("Aggregate type id {0} is not registered")]
+    UnknownAggregateType(u32),
+    #[error("Scan limit must be at least 1")]
+    InvalidScanLimit,
+}
+
+#[derive(Error, Debug)]
+pub enum CommandError<E> {
+    // shown as the aggregate's own error, `dispatch` adds the "Command rejected" context
+    #[error(transparent)]
+    Domain(E),
+    #[error(transparent)]
+    Framework(#[from] FrameworkError),
 }
@@ -5,9 +5,53 @@ use crate::{aggregate::Aggregate, as_any::AsAny, Result};
 
 pub type EventTypeId = u32;
 
+// 32 bit FNV-1a of `name`, for type ids derived from stable names,
+// the derive macros use the same hash
+pub const fn type_id_from_name(name: &str) -> u32 {
+    let bytes = name.as_bytes();
+    let mut hash: u32 = 0x811c_9dc5;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        hash = (hash ^ bytes[i] as u32).wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+
+    hash
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventType {
+    pub id: EventTypeId,
+    // stable name for tooling and exports, unlike the Rust type name it survives refactoring
+    pub name: &'static str,
+    // schema revision, bumped whenever the serialized form changes
+    pub revision: u32,
+}
+
 pub trait Event: Sync + Send + AsAny {
     fn type_id(&self) -> EventTypeId;
-    fn version(&self) -> u32;
+    fn type_name(&self) -> &'static str;
+    fn revision(&self) -> u32 {
+        1
+    }
+    // every type `type_id` can return, checked for collisions when the aggregate is registered
+    fn event_types() -> &'static [EventType]
+    where
+        Self: Sized;
+}
+
+pub struct VersionedEvent<E> {
+    pub version: u32,
+    pub event: E,
+}
+
+// event read from a store's global log, `position` orders events across all streams
+pub struct GlobalEvent<E> {
+    pub position: u64,
+    pub aggregate_id: u64,
+    pub version: u32,
+    pub event: E,
 }
 
 pub trait EventStore {
@@ -15,15 +59,32 @@ pub trait EventStore {
         &self,
         aggregate_id: u64,
         from_version: u32,
-    ) -> impl Future<Output = Result<Vec<A::Event>>> + Send
+    ) -> impl Future<Output = Result<Vec<VersionedEvent<A::Event>>>> + Send
     where
         A: Aggregate;
 
+    // events are stored with versions starting at `expected_version + 1`,
+    // and the save fails with `ConcurrencyError` if the stream is not at `expected_version`.
     fn save<A>(
         &self,
         aggregate_id: u64,
+        expected_version: u32,
         events: &[A::Event],
     ) -> impl Future<Output = Result<()>> + Send
     where
         A: Aggregate;
+
+    // ends the stream, further reads and saves fail with `AggregateDeleted`
+    fn tombstone<A>(&self, aggregate_id: u64) -> impl Future<Output = Result<()>> + Send
+    where
+        A: Aggregate;
+
+    // destroys the key the stream's payloads are encrypted with, reads then fail with
+    // `AggregateShredded`. Stores that don't encrypt have nothing to destroy
+    fn shred<A>(&self, _aggregate_id: u64) -> impl Future<Output = Result<()>> + Send
+    where
+        A: Aggregate,
+    {
+        async { Ok(()) }
+    }
 }
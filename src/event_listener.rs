@@ -1,8 +1,9 @@
 use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
 
 use crate::{
-    aggregate::Aggregate,
+    aggregate::{Aggregate, AggregateTypeId},
     event::{Event, EventTypeId},
+    telemetry::{counter, warning},
     Result,
 };
 
@@ -10,7 +11,7 @@ type BoxedEventCallback = Box<dyn Fn(&dyn Event) -> Result<()> + Sync + Send>;
 
 #[derive(Default)]
 pub struct EventListener {
-    callbacks: BTreeMap<EventTypeId, BoxedEventCallback>,
+    callbacks: BTreeMap<(AggregateTypeId, EventTypeId), BoxedEventCallback>,
 }
 
 impl EventListener {
@@ -25,18 +26,37 @@ impl EventListener {
         A: Aggregate + 'static,
     {
         for e in &events {
-            if let Some(callback) = self.callbacks.get(&e.type_id()) {
-                callback(e)?;
+            if let Some(callback) = self.callbacks.get(&(A::type_id(), e.type_id())) {
+                if let Err(error) = callback(e) {
+                    counter!(
+                        "framework_listener_failures_total",
+                        "aggregate" => A::type_name(),
+                        "event" => e.type_name(),
+                    );
+                    warning!(
+                        aggregate = A::type_name(),
+                        event = e.type_name(),
+                        error = error,
+                        "event listener failed"
+                    );
+
+                    return Err(error);
+                }
             }
         }
 
         Ok(())
     }
 
-    pub fn register_callback<F>(&mut self, event_type_id: EventTypeId, callback: F)
-    where
+    pub fn register_callback<F>(
+        &mut self,
+        aggregate_type_id: AggregateTypeId,
+        event_type_id: EventTypeId,
+        callback: F,
+    ) where
         F: Fn(&dyn Event) -> Result<()> + Sync + Send + 'static,
     {
-        self.callbacks.insert(event_type_id, Box::new(callback));
+        self.callbacks
+            .insert((aggregate_type_id, event_type_id), Box::new(callback));
     }
 }
@@ -0,0 +1,924 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{
+    aggregate::{Aggregate, AggregateTypeId},
+    cipher::{Cipher, NoCipher},
+    codec::{Codec, CodecId},
+    error::FrameworkError,
+    event::{EventStore, VersionedEvent},
+    snapshot::SnapshotStore,
+    Result,
+};
+
+// records are framed as `[length: u32][crc32 of payload: u32][payload]`, little endian
+const HEADER_LEN: usize = 8;
+
+const EVENTS_RECORD: u8 = 1;
+const TOMBSTONE_RECORD: u8 = 2;
+const SNAPSHOT_RECORD: u8 = 3;
+const SNAPSHOT_DELETE_RECORD: u8 = 4;
+
+const SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+// the snapshot log is rewritten once it is at least this large and mostly superseded records
+const COMPACTION_MIN_LEN: u64 = 1024 * 1024;
+
+fn io_error(e: io::Error) -> FrameworkError {
+    FrameworkError::DatabaseError(e.to_string())
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(HEADER_LEN + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    record.extend_from_slice(payload);
+    record
+}
+
+// payload of the record at the start of `data`, `None` if it is torn or fails its checksum
+fn unframe(data: &[u8]) -> Option<&[u8]> {
+    let mut header = Reader(data);
+    let len = header.u32()? as usize;
+    let crc = header.u32()?;
+    let payload = header.bytes(len)?;
+
+    (crc32fast::hash(payload) == crc).then_some(payload)
+}
+
+// scans the records of a log file, truncating it after the last intact record when `repair` is set
+fn recover<F>(path: &Path, repair: bool, mut f: F) -> Result<u64>
+where
+    F: FnMut(u64, &[u8]) -> Result<()>,
+{
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(io_error(e)),
+    };
+
+    let mut offset = 0;
+    while offset < data.len() {
+        let Some(payload) = unframe(&data[offset..]) else {
+            break;
+        };
+        f(offset as u64, payload)?;
+        offset += HEADER_LEN + payload.len();
+    }
+
+    if offset < data.len() {
+        // a torn write leaves a record running to the end of the file, records after a bad one
+        // mean the log is damaged rather than torn
+        let len = Reader(&data[offset..]).u32().map(|len| len as usize);
+        let torn = len.is_none_or(|len| offset + HEADER_LEN + len >= data.len());
+
+        // only the tail of the log being written to can hold a torn write
+        if !repair || !torn {
+            return Err(FrameworkError::DatabaseError(format!(
+                "corrupt record in {} at offset {}",
+                path.display(),
+                offset
+            )));
+        }
+
+        let file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(io_error)?;
+        file.set_len(offset as u64).map_err(io_error)?;
+        file.sync_all().map_err(io_error)?;
+    }
+
+    Ok(offset as u64)
+}
+
+fn read_record(path: &Path, offset: u64) -> Result<Vec<u8>> {
+    let mut file = File::open(path).map_err(io_error)?;
+    file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+
+    let mut header = [0; HEADER_LEN];
+    file.read_exact(&mut header).map_err(io_error)?;
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+
+    let mut record = header.to_vec();
+    record.resize(HEADER_LEN + len, 0);
+    file.read_exact(&mut record[HEADER_LEN..])
+        .map_err(io_error)?;
+
+    match unframe(&record) {
+        Some(payload) => Ok(payload.to_vec()),
+        None => Err(FrameworkError::DatabaseError(format!(
+            "corrupt record in {} at offset {}",
+            path.display(),
+            offset
+        ))),
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(io_error)
+}
+
+// makes creating, renaming or removing a file in `dir` durable, the file's own fsync doesn't cover
+// its directory entry
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(io_error)?;
+    #[cfg(not(unix))]
+    let _ = dir;
+
+    Ok(())
+}
+
+fn corrupt_payload() -> FrameworkError {
+    FrameworkError::DatabaseError(String::from("malformed record payload"))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8)
+            .map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+}
+
+struct Batch {
+    segment: u64,
+    offset: u64,
+    first_version: u32,
+    count: u32,
+}
+
+#[derive(Default)]
+struct Stream {
+    version: u32,
+    tombstoned: bool,
+    batches: Vec<Batch>,
+}
+
+struct EventLog {
+    dir: PathBuf,
+    segment_size: u64,
+    segment: u64,
+    file: File,
+    len: u64,
+    streams: BTreeMap<(AggregateTypeId, u64), Stream>,
+}
+
+impl EventLog {
+    fn segment_path(dir: &Path, segment: u64) -> PathBuf {
+        dir.join(format!("events-{:08}.log", segment))
+    }
+
+    fn open(dir: &Path, segment_size: u64) -> Result<Self> {
+        fs::create_dir_all(dir).map_err(io_error)?;
+
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(dir).map_err(io_error)? {
+            let name = entry.map_err(io_error)?.file_name();
+            let segment = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("events-"))
+                .and_then(|name| name.strip_suffix(".log"))
+                .and_then(|segment| segment.parse::<u64>().ok());
+            if let Some(segment) = segment {
+                segments.push(segment);
+            }
+        }
+        segments.sort_unstable();
+
+        let mut streams = BTreeMap::new();
+        let mut len = 0;
+        for (i, &segment) in segments.iter().enumerate() {
+            let last = i + 1 == segments.len();
+            len = recover(
+                &Self::segment_path(dir, segment),
+                last,
+                |offset, payload| Self::index(&mut streams, segment, offset, payload),
+            )?;
+        }
+
+        let segment = segments.last().copied().unwrap_or(0);
+        let file = open_append(&Self::segment_path(dir, segment))?;
+        sync_dir(dir)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            segment_size,
+            segment,
+            file,
+            len,
+            streams,
+        })
+    }
+
+    fn index(
+        streams: &mut BTreeMap<(AggregateTypeId, u64), Stream>,
+        segment: u64,
+        offset: u64,
+        payload: &[u8],
+    ) -> Result<()> {
+        let mut payload = Reader(payload);
+        let kind = payload.u8().ok_or_else(corrupt_payload)?;
+        let aggregate_type = payload.u32().ok_or_else(corrupt_payload)?;
+        let aggregate_id = payload.u64().ok_or_else(corrupt_payload)?;
+
+        let stream = streams.entry((aggregate_type, aggregate_id)).or_default();
+        match kind {
+            EVENTS_RECORD => {
+                let first_version = payload.u32().ok_or_else(corrupt_payload)?;
+                let _codec = payload.u8().ok_or_else(corrupt_payload)?;
+                let count = payload
+                    .u32()
+                    .filter(|&count| count > 0)
+                    .ok_or_else(corrupt_payload)?;
+
+                stream.version = first_version + count - 1;
+                stream.batches.push(Batch {
+                    segment,
+                    offset,
+                    first_version,
+                    count,
+                });
+            }
+            TOMBSTONE_RECORD => {
+                stream.tombstoned = true;
+                stream.batches.clear();
+            }
+            _ => return Err(corrupt_payload()),
+        }
+
+        Ok(())
+    }
+
+    fn append(&mut self, payload: &[u8]) -> Result<(u64, u64)> {
+        let record = frame(payload);
+
+        if self.len > 0 && self.len + record.len() as u64 > self.segment_size {
+            // the log moves on only once the next segment exists
+            let file = open_append(&Self::segment_path(&self.dir, self.segment + 1))?;
+            sync_dir(&self.dir)?;
+
+            self.segment += 1;
+            self.file = file;
+            self.len = 0;
+        }
+
+        let offset = self.len;
+        let written = self
+            .file
+            .write_all(&record)
+            .and_then(|_| self.file.sync_data());
+        if let Err(e) = written {
+            // drop whatever part of the record made it to disk, recovery would do the same
+            let _ = self.file.set_len(offset);
+            return Err(io_error(e));
+        }
+        self.len += record.len() as u64;
+
+        Ok((self.segment, offset))
+    }
+}
+
+// append-only event log split into segment files, with an in-memory index of each stream's records
+//
+// every append is fsynced before it returns, on the calling task and under the log's lock, so a
+// slow disk stalls the executor thread and other appends until the write is durable. Use a
+// multi-threaded runtime, or move calls onto blocking threads where that matters.
+pub struct FileEventStore<C, K = NoCipher>
+where
+    C: Codec,
+{
+    log: Mutex<EventLog>,
+    cipher: K,
+    _codec: PhantomData<C>,
+}
+
+impl<C> FileEventStore<C>
+where
+    C: Codec,
+{
+    pub fn open<P>(dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with_segment_size(dir, SEGMENT_SIZE)
+    }
+
+    pub fn open_with_segment_size<P>(dir: P, segment_size: u64) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self {
+            log: Mutex::new(EventLog::open(dir.as_ref(), segment_size)?),
+            cipher: NoCipher,
+            _codec: PhantomData,
+        })
+    }
+
+    // event payloads are encrypted with `cipher` before they're stored
+    pub fn with_cipher<K>(self, cipher: K) -> FileEventStore<C, K>
+    where
+        K: Cipher,
+    {
+        FileEventStore {
+            log: self.log,
+            cipher,
+            _codec: PhantomData,
+        }
+    }
+}
+
+impl<C, K> FileEventStore<C, K>
+where
+    C: Codec,
+{
+    fn with_log<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut EventLog) -> Result<T>,
+    {
+        let mut log = self
+            .log
+            .lock()
+            .map_err(|e| FrameworkError::DatabaseError(e.to_string()))?;
+
+        f(&mut log)
+    }
+}
+
+impl<C, K> EventStore for FileEventStore<C, K>
+where
+    C: Codec + Sync + Send,
+    K: Cipher + 'static,
+{
+    async fn read<A>(
+        &self,
+        aggregate_id: u64,
+        from_version: u32,
+    ) -> Result<Vec<VersionedEvent<A::Event>>>
+    where
+        A: Aggregate,
+    {
+        let records = self.with_log(|log| {
+            let Some(stream) = log.streams.get(&(A::type_id(), aggregate_id)) else {
+                return Ok(Vec::new());
+            };
+            if stream.tombstoned {
+                return Err(FrameworkError::AggregateDeleted(aggregate_id));
+            }
+
+            let mut events = Vec::new();
+            for batch in &stream.batches {
+                if batch.first_version + batch.count - 1 <= from_version {
+                    continue;
+                }
+
+                let record = read_record(
+                    &EventLog::segment_path(&log.dir, batch.segment),
+                    batch.offset,
+                )?;
+                let mut payload = Reader(&record);
+                // kind, aggregate type, aggregate id, first version
+                payload.bytes(17).ok_or_else(corrupt_payload)?;
+                let codec_id: CodecId = payload.u8().ok_or_else(corrupt_payload)?;
+                payload.u32().ok_or_else(corrupt_payload)?;
+
+                for version in batch.first_version..batch.first_version + batch.count {
+                    let len = payload.u32().ok_or_else(corrupt_payload)? as usize;
+                    let data = payload.bytes(len).ok_or_else(corrupt_payload)?;
+                    if version > from_version {
+                        events.push((version, codec_id, data.to_vec()));
+                    }
+                }
+            }
+
+            Ok(events)
+        })?;
+
+        let mut events = Vec::with_capacity(records.len());
+        for (version, codec_id, data) in records {
+            let data = self.cipher.decrypt::<A>(aggregate_id, data).await?;
+            events.push(VersionedEvent {
+                version,
+                event: C::decode_tagged(codec_id, &data)?,
+            });
+        }
+
+        Ok(events)
+    }
+
+    async fn save<A>(
+        &self,
+        aggregate_id: u64,
+        expected_version: u32,
+        events: &[A::Event],
+    ) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let mut encrypted = Vec::with_capacity(events.len());
+        for event in events {
+            let data = C::encode(event)?;
+            encrypted.push(self.cipher.encrypt::<A>(aggregate_id, data).await?);
+        }
+        let events = encrypted;
+
+        self.with_log(|log| {
+            let key = (A::type_id(), aggregate_id);
+            if let Some(stream) = log.streams.get(&key) {
+                if stream.tombstoned {
+                    return Err(FrameworkError::AggregateDeleted(aggregate_id));
+                }
+                if stream.version != expected_version {
+                    return Err(FrameworkError::ConcurrencyError);
+                }
+            } else if expected_version != 0 {
+                return Err(FrameworkError::ConcurrencyError);
+            }
+
+            if events.is_empty() {
+                return Ok(());
+            }
+
+            // the whole batch is a single record, so a torn write loses all of it or none
+            let mut payload = Vec::new();
+            payload.push(EVENTS_RECORD);
+            payload.extend_from_slice(&A::type_id().to_le_bytes());
+            payload.extend_from_slice(&aggregate_id.to_le_bytes());
+            payload.extend_from_slice(&(expected_version + 1).to_le_bytes());
+            payload.push(C::ID);
+            payload.extend_from_slice(&(events.len() as u32).to_le_bytes());
+            for data in &events {
+                payload.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                payload.extend_from_slice(data);
+            }
+
+            let (segment, offset) = log.append(&payload)?;
+
+            let stream = log.streams.entry(key).or_default();
+            stream.version = expected_version + events.len() as u32;
+            stream.batches.push(Batch {
+                segment,
+                offset,
+                first_version: expected_version + 1,
+                count: events.len() as u32,
+            });
+
+            Ok(())
+        })
+    }
+
+    async fn tombstone<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.with_log(|log| {
+            let mut payload = Vec::new();
+            payload.push(TOMBSTONE_RECORD);
+            payload.extend_from_slice(&A::type_id().to_le_bytes());
+            payload.extend_from_slice(&aggregate_id.to_le_bytes());
+
+            log.append(&payload)?;
+
+            let stream = log.streams.entry((A::type_id(), aggregate_id)).or_default();
+            stream.tombstoned = true;
+            stream.batches.clear();
+
+            Ok(())
+        })
+    }
+
+    async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.cipher.shred::<A>(aggregate_id).await
+    }
+}
+
+struct SnapshotLog {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    // total length of the records still referenced by `snapshots`
+    live: u64,
+    // offset and framed length of the latest snapshot of each aggregate
+    snapshots: BTreeMap<(AggregateTypeId, u64), (u64, u64)>,
+}
+
+impl SnapshotLog {
+    fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).map_err(io_error)?;
+
+        let path = dir.join("snapshots.log");
+        // left behind by an interrupted compaction, the log it was replacing is still intact
+        let _ = fs::remove_file(path.with_extension("compact"));
+
+        let mut snapshots = BTreeMap::new();
+        let len = recover(&path, true, |offset, payload| {
+            let record_len = (HEADER_LEN + payload.len()) as u64;
+            let mut payload = Reader(payload);
+            let kind = payload.u8().ok_or_else(corrupt_payload)?;
+            let aggregate_type = payload.u32().ok_or_else(corrupt_payload)?;
+            let aggregate_id = payload.u64().ok_or_else(corrupt_payload)?;
+
+            match kind {
+                SNAPSHOT_RECORD => {
+                    snapshots.insert((aggregate_type, aggregate_id), (offset, record_len));
+                }
+                SNAPSHOT_DELETE_RECORD => {
+                    snapshots.remove(&(aggregate_type, aggregate_id));
+                }
+                _ => return Err(corrupt_payload()),
+            }
+
+            Ok(())
+        })?;
+
+        let live = snapshots.values().map(|(_, len)| len).sum();
+        let file = open_append(&path)?;
+        sync_dir(dir)?;
+
+        Ok(Self {
+            path,
+            file,
+            len,
+            live,
+            snapshots,
+        })
+    }
+
+    fn append(&mut self, payload: &[u8]) -> Result<(u64, u64)> {
+        let record = frame(payload);
+
+        let offset = self.len;
+        let written = self
+            .file
+            .write_all(&record)
+            .and_then(|_| self.file.sync_data());
+        if let Err(e) = written {
+            let _ = self.file.set_len(offset);
+            return Err(io_error(e));
+        }
+        self.len += record.len() as u64;
+
+        Ok((offset, record.len() as u64))
+    }
+
+    fn compact_if_needed(&mut self) -> Result<()> {
+        if self.len < COMPACTION_MIN_LEN || self.len < 2 * self.live {
+            return Ok(());
+        }
+
+        let data = fs::read(&self.path).map_err(io_error)?;
+        let compact_path = self.path.with_extension("compact");
+
+        let mut compacted = Vec::with_capacity(self.live as usize);
+        let mut snapshots = BTreeMap::new();
+        for (key, &(offset, len)) in &self.snapshots {
+            let record = &data[offset as usize..(offset + len) as usize];
+            snapshots.insert(*key, (compacted.len() as u64, len));
+            compacted.extend_from_slice(record);
+        }
+
+        let mut file = File::create(&compact_path).map_err(io_error)?;
+        file.write_all(&compacted)
+            .and_then(|_| file.sync_all())
+            .map_err(io_error)?;
+        fs::rename(&compact_path, &self.path).map_err(io_error)?;
+        if let Some(dir) = self.path.parent() {
+            sync_dir(dir)?;
+        }
+
+        self.file = open_append(&self.path)?;
+        self.len = compacted.len() as u64;
+        self.live = self.len;
+        self.snapshots = snapshots;
+
+        Ok(())
+    }
+}
+
+// snapshot log that is rewritten with only the latest snapshots once superseded ones pile up,
+// writes and compactions block on fsync like `FileEventStore` appends
+pub struct FileSnapshotStore<C>
+where
+    C: Codec,
+{
+    log: Mutex<SnapshotLog>,
+    _codec: PhantomData<C>,
+}
+
+impl<C> FileSnapshotStore<C>
+where
+    C: Codec,
+{
+    pub fn open<P>(dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self {
+            log: Mutex::new(SnapshotLog::open(dir.as_ref())?),
+            _codec: PhantomData,
+        })
+    }
+
+    fn with_log<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut SnapshotLog) -> Result<T>,
+    {
+        let mut log = self
+            .log
+            .lock()
+            .map_err(|e| FrameworkError::DatabaseError(e.to_string()))?;
+
+        f(&mut log)
+    }
+}
+
+impl<C> SnapshotStore for FileSnapshotStore<C>
+where
+    C: Codec + Sync + Send,
+{
+    async fn read<A>(&self, aggregate_id: u64) -> Result<Option<(u32, A)>>
+    where
+        A: Aggregate,
+    {
+        self.with_log(|log| {
+            let Some(&(offset, _)) = log.snapshots.get(&(A::type_id(), aggregate_id)) else {
+                return Ok(None);
+            };
+
+            let record = read_record(&log.path, offset)?;
+            let mut payload = Reader(&record);
+            // kind, aggregate type, aggregate id
+            payload.bytes(13).ok_or_else(corrupt_payload)?;
+            let version = payload.u32().ok_or_else(corrupt_payload)?;
+            let codec_id = payload.u8().ok_or_else(corrupt_payload)?;
+
+            Ok(Some((version, C::decode_tagged(codec_id, payload.0)?)))
+        })
+    }
+
+    async fn save<A>(&self, aggregate_id: u64, version: u32, aggregate: &A) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let data = C::encode(aggregate)?;
+
+        self.with_log(|log| {
+            let mut payload = Vec::new();
+            payload.push(SNAPSHOT_RECORD);
+            payload.extend_from_slice(&A::type_id().to_le_bytes());
+            payload.extend_from_slice(&aggregate_id.to_le_bytes());
+            payload.extend_from_slice(&version.to_le_bytes());
+            payload.push(C::ID);
+            payload.extend_from_slice(&data);
+
+            let (offset, len) = log.append(&payload)?;
+
+            if let Some((_, previous)) = log
+                .snapshots
+                .insert((A::type_id(), aggregate_id), (offset, len))
+            {
+                log.live -= previous;
+            }
+            log.live += len;
+
+            log.compact_if_needed()
+        })
+    }
+
+    async fn delete<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.with_log(|log| {
+            if !log.snapshots.contains_key(&(A::type_id(), aggregate_id)) {
+                return Ok(());
+            }
+
+            let mut payload = Vec::new();
+            payload.push(SNAPSHOT_DELETE_RECORD);
+            payload.extend_from_slice(&A::type_id().to_le_bytes());
+            payload.extend_from_slice(&aggregate_id.to_le_bytes());
+
+            log.append(&payload)?;
+
+            if let Some((_, previous)) = log.snapshots.remove(&(A::type_id(), aggregate_id)) {
+                log.live -= previous;
+            }
+
+            log.compact_if_needed()
+        })
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "json"))]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{
+        codec::JsonCodec,
+        testing::{self, TestAggregate, TestEvent},
+    };
+
+    #[tokio::test]
+    async fn event_store_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        // small segments, so the checks span several of them
+        let store = FileEventStore::<JsonCodec>::open_with_segment_size(dir.path(), 256).unwrap();
+
+        testing::event_store_conformance(&store).await;
+    }
+
+    #[tokio::test]
+    async fn snapshot_store_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileSnapshotStore::<JsonCodec>::open(dir.path()).unwrap();
+
+        testing::snapshot_store_conformance(&store).await;
+    }
+
+    #[tokio::test]
+    async fn events_survive_reopening_across_segments() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = FileEventStore::<JsonCodec>::open_with_segment_size(dir.path(), 64).unwrap();
+        for version in 0..8 {
+            store
+                .save::<TestAggregate>(1, version, &[TestEvent::Added(version as u64)])
+                .await
+                .unwrap();
+        }
+        drop(store);
+
+        let segments = fs::read_dir(dir.path()).unwrap().count();
+        assert!(segments > 1);
+
+        let store = FileEventStore::<JsonCodec>::open_with_segment_size(dir.path(), 64).unwrap();
+        let events = store.read::<TestAggregate>(1, 6).await.unwrap();
+        let events = events
+            .into_iter()
+            .map(|e| (e.version, e.event))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![(7, TestEvent::Added(6)), (8, TestEvent::Added(7))]
+        );
+    }
+
+    // three batches of one event each in the first segment, returns where each record ends
+    async fn write_batches(dir: &Path) -> Vec<u64> {
+        let store = FileEventStore::<JsonCodec>::open(dir).unwrap();
+        let path = EventLog::segment_path(dir, 0);
+
+        let mut ends = Vec::new();
+        for version in 0..3 {
+            store
+                .save::<TestAggregate>(1, version, &[TestEvent::Added(1)])
+                .await
+                .unwrap();
+            ends.push(fs::metadata(&path).unwrap().len());
+        }
+
+        ends
+    }
+
+    fn damage<F>(dir: &Path, f: F)
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        let path = EventLog::segment_path(dir, 0);
+        let mut data = fs::read(&path).unwrap();
+        f(&mut data);
+        fs::write(&path, data).unwrap();
+    }
+
+    async fn versions(dir: &Path) -> Vec<u32> {
+        let store = FileEventStore::<JsonCodec>::open(dir).unwrap();
+        let events = store.read::<TestAggregate>(1, 0).await.unwrap();
+
+        events.into_iter().map(|e| e.version).collect()
+    }
+
+    #[tokio::test]
+    async fn torn_record_at_the_tail_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let ends = write_batches(dir.path()).await;
+
+        damage(dir.path(), |data| data.truncate(ends[2] as usize - 3));
+        assert_eq!(versions(dir.path()).await, vec![1, 2]);
+
+        // the tail was cut off, appends carry on from the intact records
+        let store = FileEventStore::<JsonCodec>::open(dir.path()).unwrap();
+        store
+            .save::<TestAggregate>(1, 2, &[TestEvent::Removed])
+            .await
+            .unwrap();
+        drop(store);
+        assert_eq!(versions(dir.path()).await, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn truncated_header_at_the_tail_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        write_batches(dir.path()).await;
+
+        damage(dir.path(), |data| data.extend_from_slice(&[7, 0, 0, 0, 1]));
+        assert_eq!(versions(dir.path()).await, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn bad_checksum_of_the_last_record_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let ends = write_batches(dir.path()).await;
+
+        damage(dir.path(), |data| data[ends[2] as usize - 1] ^= 0xff);
+        assert_eq!(versions(dir.path()).await, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn bad_checksum_before_intact_records_fails_to_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let ends = write_batches(dir.path()).await;
+
+        damage(dir.path(), |data| data[ends[1] as usize - 1] ^= 0xff);
+        assert!(matches!(
+            FileEventStore::<JsonCodec>::open(dir.path()),
+            Err(FrameworkError::DatabaseError(_))
+        ));
+        // nothing was truncated
+        assert_eq!(
+            fs::metadata(EventLog::segment_path(dir.path(), 0))
+                .unwrap()
+                .len(),
+            ends[2]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_segment_roll_keeps_the_current_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileEventStore::<JsonCodec>::open_with_segment_size(dir.path(), 64).unwrap();
+        store
+            .save::<TestAggregate>(1, 0, &[TestEvent::Added(1)])
+            .await
+            .unwrap();
+
+        // the next segment can't be created
+        let next = EventLog::segment_path(dir.path(), 1);
+        fs::create_dir(&next).unwrap();
+        assert!(store
+            .save::<TestAggregate>(1, 1, &[TestEvent::Added(2)])
+            .await
+            .is_err());
+
+        fs::remove_dir(&next).unwrap();
+        store
+            .save::<TestAggregate>(1, 1, &[TestEvent::Added(2)])
+            .await
+            .unwrap();
+        drop(store);
+
+        assert!(next.is_file());
+        assert_eq!(versions(dir.path()).await, vec![1, 2]);
+    }
+}
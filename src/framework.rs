@@ -1,16 +1,31 @@
+use alloc::{boxed::Box, string::ToString, sync::Arc, vec::Vec};
+
 use crate::{
     aggregate::Aggregate,
-    command::Command,
-    error::FrameworkError,
+    command::{Command, ExpectedState, Position},
+    error::{CommandError, FrameworkError},
     event::{Event, EventStore, EventTypeId},
     event_listener::EventListener,
-    query::{Query, QueryHandler},
+    query::{Query, QueryHandler, ReadModelStoreRef},
     read_model::ReadModelStores,
+    registry::EventRegistry,
     repository::AggregateRepository,
+    saga::{Saga, SagaHandler, SagaStore},
     snapshot::SnapshotStore,
-    Result,
+    telemetry::{counter, histogram, span, warning, Instrument, Timer},
+    BoxFuture, Result,
 };
 
+#[cfg(feature = "std")]
+use crate::{
+    cache::AggregateCache,
+    mailbox::{MailboxGuard, Mailboxes},
+};
+
+type BoxedSagaErrorHandler = Box<dyn Fn(&'static str, &FrameworkError) + Sync + Send>;
+
+type CommandResult<T, A> = core::result::Result<T, CommandError<<A as Aggregate>::Error>>;
+
 pub struct Framework<E, S, R>
 where
     E: EventStore + 'static,
@@ -21,6 +36,13 @@ where
     snapshot_store: S,
     read_model_stores: R,
     event_listener: EventListener,
+    registry: EventRegistry,
+    sagas: Vec<SagaHandler<Self>>,
+    saga_error_handler: Option<BoxedSagaErrorHandler>,
+    #[cfg(feature = "std")]
+    mailboxes: Option<Mailboxes>,
+    #[cfg(feature = "std")]
+    cache: Option<AggregateCache>,
 }
 
 impl<E, S, R> Framework<E, S, R>
@@ -35,31 +57,241 @@ where
             snapshot_store,
             read_model_stores,
             event_listener: EventListener::new(),
+            registry: EventRegistry::new(),
+            sagas: Vec::new(),
+            saga_error_handler: None,
+            #[cfg(feature = "std")]
+            mailboxes: None,
+            #[cfg(feature = "std")]
+            cache: None,
         }
     }
 
-    pub async fn command<C>(&self, command: C) -> Result<()>
+    // Runs commands for the same aggregate one at a time, keeping up to `capacity` aggregates
+    // loaded between commands. Conflicts can then only come from other processes writing to the
+    // event store, so it's meant for single node use.
+    #[cfg(feature = "std")]
+    pub fn enable_sequential_commands(&mut self, capacity: usize) {
+        self.mailboxes = Some(Mailboxes::new(capacity));
+    }
+
+    // keeps up to `capacity` aggregates between commands, least recently used are evicted first
+    #[cfg(feature = "std")]
+    pub fn enable_aggregate_cache(&mut self, capacity: usize) {
+        self.cache = Some(AggregateCache::new(capacity));
+    }
+
+    fn repository<A>(&self) -> AggregateRepository<'_, A, E, S>
+    where
+        A: Aggregate,
+    {
+        let repository = AggregateRepository::new(&self.event_store, &self.snapshot_store);
+        #[cfg(feature = "std")]
+        let repository = repository.with_cache(self.cache.as_ref());
+
+        repository
+    }
+
+    pub async fn command<C>(&self, command: C) -> CommandResult<Position, C::Aggregate>
     where
         C: Command,
     {
-        let aggregate_id = command.aggregate_id();
+        let aggregate_type = C::Aggregate::type_name();
+        let span = span!(
+            "command",
+            aggregate = aggregate_type,
+            aggregate_id = command.aggregate_id(),
+        );
 
-        let repository = AggregateRepository::new(&self.event_store, &self.snapshot_store);
+        let result = self.execute(command).instrument(span).await;
 
-        let aggregate: C::Aggregate = repository.read(command.aggregate_id()).await?;
+        counter!(
+            "framework_commands_total",
+            "aggregate" => aggregate_type,
+            "outcome" => match &result {
+                Ok(_) => "ok",
+                Err(CommandError::Domain(_)) => "rejected",
+                Err(CommandError::Framework(_)) => "failed",
+            },
+        );
 
-        let events = aggregate.handle(command)?;
+        result
+    }
 
-        repository.save(aggregate_id, &events).await?;
+    async fn execute<C>(&self, command: C) -> CommandResult<Position, C::Aggregate>
+    where
+        C: Command,
+    {
+        let aggregate_id = command.aggregate_id();
 
-        self.read_model_stores
-            .update_read_model(aggregate_id, &events)
-            .await?;
+        #[cfg(feature = "std")]
+        let (position, events) = match &self.mailboxes {
+            Some(mailboxes) => {
+                let mut mailbox = mailboxes.lock::<C::Aggregate>(aggregate_id).await;
+                let (result, aggregate) = self.process(command, mailbox.take()).await;
+                mailbox.put(aggregate);
+                result?
+            }
+            None => {
+                let (result, aggregate) = self.process(command, None).await;
+                self.repository::<C::Aggregate>()
+                    .keep(aggregate_id, aggregate);
+                result?
+            }
+        };
+        #[cfg(not(feature = "std"))]
+        let (position, events) = self.process(command, None).await.0?;
+
+        // outside the mailbox, sagas may command the same aggregate. The command is committed by
+        // now, so saga failures are reported rather than failing it
+        for saga in &self.sagas {
+            for event in &events {
+                if let Err(error) = (saga.on_event)(self, aggregate_id, event).await {
+                    self.report_saga_error(saga.name, &error);
+                }
+            }
+        }
 
         self.event_listener
             .handle_events::<C::Aggregate>(events)
             .await?;
 
+        Ok(position)
+    }
+
+    // handles the command against `cached`, or the stored aggregate if there's none, also returns
+    // the aggregate as of the command for the next one
+    #[allow(clippy::type_complexity)]
+    async fn process<C>(
+        &self,
+        command: C,
+        cached: Option<(u32, C::Aggregate)>,
+    ) -> (
+        CommandResult<(Position, Vec<<C::Aggregate as Aggregate>::Event>), C::Aggregate>,
+        Option<(u32, C::Aggregate)>,
+    )
+    where
+        C: Command,
+    {
+        let aggregate_id = command.aggregate_id();
+        let aggregate_type = C::Aggregate::type_name();
+
+        let repository = self.repository::<C::Aggregate>();
+
+        let timer = Timer::start();
+        let aggregate = match cached {
+            Some(cached) => {
+                repository
+                    .read_from(aggregate_id, Some(cached))
+                    .instrument(span!("load"))
+                    .await
+            }
+            None => {
+                repository
+                    .read(aggregate_id)
+                    .instrument(span!("load"))
+                    .await
+            }
+        };
+        let aggregate = match aggregate {
+            Ok(aggregate) => aggregate,
+            Err(e) => return (Err(e.into()), None),
+        };
+        timer.record("command", "load", aggregate_type);
+
+        match (command.expected_state(), &aggregate) {
+            (ExpectedState::Exists, None) => {
+                return (
+                    Err(FrameworkError::AggregateNotFound(aggregate_id).into()),
+                    aggregate,
+                )
+            }
+            (ExpectedState::NotExists, Some(_)) => {
+                return (
+                    Err(FrameworkError::AggregateAlreadyExists(aggregate_id).into()),
+                    aggregate,
+                )
+            }
+            _ => {}
+        }
+
+        let exists = aggregate.is_some();
+        let (version, aggregate) = aggregate.unwrap_or_default();
+
+        let timer = Timer::start();
+        let events = match span!("handle").in_scope(|| aggregate.handle(command)) {
+            Ok(events) => events,
+            Err(e) => {
+                return (
+                    Err(CommandError::Domain(e)),
+                    exists.then_some((version, aggregate)),
+                )
+            }
+        };
+        timer.record("command", "handle", aggregate_type);
+
+        let timer = Timer::start();
+        let aggregate = match repository
+            .save(aggregate_id, version, aggregate, &events)
+            .instrument(span!("save", events = events.len()))
+            .await
+        {
+            Ok(aggregate) => aggregate,
+            // the stored aggregate may have moved on, it's read again by the next command
+            Err(e) => return (Err(e.into()), None),
+        };
+        timer.record("command", "save", aggregate_type);
+
+        let version = version + events.len() as u32;
+
+        let timer = Timer::start();
+        if let Err(e) = self
+            .read_model_stores
+            .update_read_model(aggregate_id, version, &events)
+            .instrument(span!("project"))
+            .await
+        {
+            return (Err(e.into()), aggregate);
+        }
+        timer.record("command", "project", aggregate_type);
+
+        let position = Position {
+            aggregate_type_id: C::Aggregate::type_id(),
+            aggregate_id,
+            version,
+        };
+
+        (Ok((position, events)), aggregate)
+    }
+
+    // tombstones the aggregate's stream and drops its snapshot
+    pub async fn delete<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        #[cfg(feature = "std")]
+        let _mailbox = self.clear_mailbox::<A>(aggregate_id).await;
+
+        self.repository::<A>().delete(aggregate_id).await
+    }
+
+    // destroys the aggregate's key through the event store, the snapshot and read models are
+    // purged as they may hold decrypted state
+    pub async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate + 'static,
+    {
+        #[cfg(feature = "std")]
+        let _mailbox = self.clear_mailbox::<A>(aggregate_id).await;
+        #[cfg(feature = "std")]
+        self.repository::<A>().evict(aggregate_id);
+
+        self.event_store.shred::<A>(aggregate_id).await?;
+        self.snapshot_store.delete::<A>(aggregate_id).await?;
+        self.read_model_stores
+            .purge_read_model::<A::Event>(aggregate_id)
+            .await?;
+
         Ok(())
     }
 
@@ -67,22 +299,802 @@ where
     where
         Q: Query + 'static,
     {
-        let store = self
-            .read_model_stores
-            .find::<<Q::Handler as QueryHandler<Q>>::ReadModelStore>();
+        self.query_at_least(query, None).await
+    }
 
-        let Some(store) = store else {
-            return Err(FrameworkError::NoSuchReadModelStore);
-        };
+    pub async fn query_at_least<Q>(
+        &self,
+        query: Q,
+        position: Option<Position>,
+    ) -> Result<<Q::Handler as QueryHandler<Q>>::Output>
+    where
+        Q: Query + 'static,
+    {
+        let query_type = core::any::type_name::<Q>();
+
+        async move {
+            let stores =
+                ReadModelStoreRef::<<Q::Handler as QueryHandler<Q>>::ReadModelStore>::find(
+                    &self.read_model_stores,
+                )?;
+
+            if let Some(position) = position {
+                let event = self
+                    .registry
+                    .event_type_of(position.aggregate_type_id)
+                    .ok_or(FrameworkError::UnknownAggregateType(
+                        position.aggregate_type_id,
+                    ))?;
+
+                // stores projecting other aggregates' events have nothing to catch up on
+                if let Some(version) = stores.position(event, position.aggregate_id).await? {
+                    // events the read model has yet to project
+                    histogram!(
+                        "framework_read_model_lag",
+                        position.version.saturating_sub(version),
+                        "query" => query_type,
+                    );
+
+                    if version < position.version {
+                        return Err(FrameworkError::ReadModelBehind(position.version, version));
+                    }
+                }
+            }
+
+            let timer = Timer::start();
+            let output = Q::Handler::handle(stores, query).await;
+            timer.record("query", "handle", query_type);
 
-        Q::Handler::handle(store, query).await
+            output
+        }
+        .instrument(span!("query", query = query_type))
+        .await
+    }
+
+    // fails on aggregate or event type ids that collide with ones registered before
+    pub fn register_aggregate<A>(&mut self) -> Result<()>
+    where
+        A: Aggregate + 'static,
+    {
+        self.registry.register::<A>()
+    }
+
+    pub fn registry(&self) -> &EventRegistry {
+        &self.registry
     }
 
-    pub fn register_event_callback<F>(&mut self, event_type_id: EventTypeId, callback: F)
+    pub fn register_event_callback<A, F>(&mut self, event_type_id: EventTypeId, callback: F)
     where
+        A: Aggregate,
         F: Fn(&dyn Event) -> Result<()> + Sync + Send + 'static,
     {
         self.event_listener
-            .register_callback(event_type_id, callback)
+            .register_callback(A::type_id(), event_type_id, callback)
+    }
+
+    pub fn register_saga<G, T>(&mut self, saga_store: T)
+    where
+        G: Saga,
+        T: SagaStore,
+        E: Sync,
+        S: Sync,
+        R: Sync,
+    {
+        let saga_store = Arc::new(saga_store);
+        let timeout_saga_store = saga_store.clone();
+
+        self.sagas.push(SagaHandler {
+            name: core::any::type_name::<G>(),
+            on_event: Box::new(move |framework, aggregate_id, event| {
+                framework.handle_saga_event::<G, T>(saga_store.clone(), aggregate_id, event)
+            }),
+            on_timeout: Box::new(move |framework, now| {
+                framework.handle_saga_timeout::<G, T>(timeout_saga_store.clone(), now)
+            }),
+        });
+    }
+
+    // called with the saga's type name when a saga fails handling a committed command's events
+    pub fn register_saga_error_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(&'static str, &FrameworkError) + Sync + Send + 'static,
+    {
+        self.saga_error_handler = Some(Box::new(handler));
+    }
+
+    fn report_saga_error(&self, saga: &'static str, error: &FrameworkError) {
+        counter!("framework_saga_failures_total", "saga" => saga);
+        warning!(saga = saga, error = error, "saga failed");
+
+        if let Some(handler) = &self.saga_error_handler {
+            handler(saga, error);
+        }
+    }
+
+    // a failing saga is reported like in `command`, the others still get their timeouts
+    pub async fn handle_saga_timeouts(&self, now: u64) {
+        for saga in &self.sagas {
+            if let Err(error) = (saga.on_timeout)(self, now).await {
+                self.report_saga_error(saga.name, &error);
+            }
+        }
+    }
+
+    fn handle_saga_event<'a, G, T>(
+        &'a self,
+        saga_store: Arc<T>,
+        aggregate_id: u64,
+        event: &'a dyn Event,
+    ) -> BoxFuture<'a, Result<()>>
+    where
+        G: Saga,
+        T: SagaStore,
+        E: Sync,
+        S: Sync,
+        R: Sync,
+    {
+        Box::pin(async move {
+            let Some(event) = event
+                .as_any()
+                .downcast_ref::<<G::Aggregate as Aggregate>::Event>()
+            else {
+                return Ok(());
+            };
+
+            let Some(correlation_id) = G::correlation_id(aggregate_id, event) else {
+                return Ok(());
+            };
+
+            let commands =
+                Self::update_saga::<G, T, _>(&*saga_store, correlation_id, true, |saga| {
+                    saga.handle(event).map(Some)
+                })
+                .await?;
+
+            self.dispatch_saga_commands(commands).await
+        })
+    }
+
+    fn handle_saga_timeout<G, T>(&self, saga_store: Arc<T>, now: u64) -> BoxFuture<'_, Result<()>>
+    where
+        G: Saga,
+        T: SagaStore,
+        E: Sync,
+        S: Sync,
+        R: Sync,
+    {
+        Box::pin(async move {
+            for correlation_id in saga_store.expired::<G>(now).await? {
+                // the deadline may have been moved since `expired`
+                let commands =
+                    Self::update_saga::<G, T, _>(&*saga_store, correlation_id, false, |saga| {
+                        if saga.deadline().is_some_and(|deadline| deadline <= now) {
+                            saga.handle_timeout().map(Some)
+                        } else {
+                            Ok(None)
+                        }
+                    })
+                    .await?;
+
+                self.dispatch_saga_commands(commands).await?;
+            }
+
+            Ok(())
+        })
+    }
+
+    // Read-modify-write of the saga's state, redone when another event for the same correlation
+    // id saved it in between, which means that one made progress. `update` returns `None` to
+    // leave the saga as it is, a saga that doesn't exist is only created if `create` is set.
+    async fn update_saga<G, T, F>(
+        saga_store: &T,
+        correlation_id: u64,
+        create: bool,
+        mut update: F,
+    ) -> Result<Vec<G::Command>>
+    where
+        G: Saga,
+        T: SagaStore,
+        F: FnMut(&mut G) -> Result<Option<Vec<G::Command>>> + Send,
+    {
+        loop {
+            let (version, mut saga) = match saga_store.read::<G>(correlation_id).await? {
+                Some(saga) => saga,
+                None if create => (0, G::default()),
+                None => return Ok(Vec::new()),
+            };
+
+            let Some(commands) = update(&mut saga)? else {
+                return Ok(Vec::new());
+            };
+
+            match saga_store.save(correlation_id, version, &saga).await {
+                Err(FrameworkError::ConcurrencyError) => continue,
+                result => return result.map(|_| commands),
+            }
+        }
+    }
+
+    async fn dispatch_saga_commands<C>(&self, commands: Vec<C>) -> Result<()>
+    where
+        C: Command + Send + 'static,
+        E: Sync,
+        S: Sync,
+        R: Sync,
+    {
+        for command in commands {
+            self.dispatch(command).await?;
+        }
+
+        Ok(())
+    }
+
+    // waits for the aggregate's queued commands and drops the aggregate they kept
+    #[cfg(feature = "std")]
+    async fn clear_mailbox<A>(&self, aggregate_id: u64) -> Option<MailboxGuard>
+    where
+        A: Aggregate,
+    {
+        let mut mailbox = self.mailboxes.as_ref()?.lock::<A>(aggregate_id).await;
+        mailbox.clear();
+
+        Some(mailbox)
+    }
+
+    // type-erased dispatch, domain errors are reported as `CommandRejected`
+    pub(crate) fn dispatch<C>(&self, command: C) -> BoxFuture<'_, Result<Position>>
+    where
+        C: Command + Send + 'static,
+        E: Sync,
+        S: Sync,
+        R: Sync,
+    {
+        Box::pin(async move {
+            match self.command(command).await {
+                Ok(position) => Ok(position),
+                Err(CommandError::Domain(e)) => Err(FrameworkError::CommandRejected(e.to_string())),
+                Err(CommandError::Framework(e)) => Err(e),
+            }
+        })
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "sqlite", feature = "json"))]
+mod tests {
+    use alloc::{string::String, vec};
+    use std::sync::Mutex;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{
+        cipher::Cipher,
+        codec::JsonCodec,
+        event::{EventType, VersionedEvent},
+        read_model::{ReadModel, ReadModelStore, ReadModelUpdate},
+        saga::SagaTypeId,
+        snapshot::DummySnapshotStore,
+        sqlite::{SqliteDatabase, SqliteEventStore},
+        testing::{
+            InMemoryReadModelStore, InMemorySagaStore, TestAggregate, TestCommand, TestEvent,
+            TestReadModel,
+        },
+    };
+
+    #[derive(Default, Serialize, Deserialize)]
+    struct CountingSaga {
+        events: u64,
+    }
+
+    impl Saga for CountingSaga {
+        type Aggregate = TestAggregate;
+        type Command = TestCommand;
+
+        fn type_id() -> SagaTypeId {
+            1
+        }
+
+        fn correlation_id(_aggregate_id: u64, _event: &TestEvent) -> Option<u64> {
+            Some(1)
+        }
+
+        fn handle(&mut self, event: &TestEvent) -> Result<Vec<TestCommand>> {
+            self.events += 1;
+
+            Ok(match event {
+                // rejected, aggregate 999 has nothing to remove
+                TestEvent::Renamed(_) => vec![TestCommand {
+                    aggregate_id: 999,
+                    event: TestEvent::Removed,
+                }],
+                _ => Vec::new(),
+            })
+        }
+    }
+
+    // times out once created, unless `FAILS` is set
+    #[derive(Default, Serialize, Deserialize)]
+    struct TimingSaga<const FAILS: bool> {
+        timed_out: bool,
+    }
+
+    impl<const FAILS: bool> Saga for TimingSaga<FAILS> {
+        type Aggregate = TestAggregate;
+        type Command = TestCommand;
+
+        fn type_id() -> SagaTypeId {
+            2 + FAILS as SagaTypeId
+        }
+
+        fn correlation_id(_aggregate_id: u64, _event: &TestEvent) -> Option<u64> {
+            Some(1)
+        }
+
+        fn handle(&mut self, _event: &TestEvent) -> Result<Vec<TestCommand>> {
+            Ok(Vec::new())
+        }
+
+        fn deadline(&self) -> Option<u64> {
+            (!self.timed_out).then_some(1)
+        }
+
+        fn handle_timeout(&mut self) -> Result<Vec<TestCommand>> {
+            if FAILS {
+                return Err(FrameworkError::CommandRejected(String::from("timed out")));
+            }
+
+            self.timed_out = true;
+            Ok(Vec::new())
+        }
+    }
+
+    // yields between reading and saving a saga, so concurrent events interleave there
+    struct YieldingSagaStore(Arc<InMemorySagaStore<JsonCodec>>);
+
+    impl SagaStore for YieldingSagaStore {
+        async fn read<S>(&self, correlation_id: u64) -> Result<Option<(u32, S)>>
+        where
+            S: Saga,
+        {
+            let saga = self.0.read(correlation_id).await;
+            tokio::task::yield_now().await;
+            saga
+        }
+
+        async fn save<S>(&self, correlation_id: u64, expected_version: u32, saga: &S) -> Result<()>
+        where
+            S: Saga,
+        {
+            self.0.save(correlation_id, expected_version, saga).await
+        }
+
+        async fn expired<S>(&self, now: u64) -> Result<Vec<u64>>
+        where
+            S: Saga,
+        {
+            self.0.expired::<S>(now).await
+        }
+    }
+
+    type TestFramework = Framework<SqliteEventStore<JsonCodec>, DummySnapshotStore, ()>;
+
+    fn framework() -> (TestFramework, Arc<InMemorySagaStore<JsonCodec>>) {
+        let event_store = SqliteDatabase::open_in_memory()
+            .unwrap()
+            .event_store::<JsonCodec>();
+        let mut framework = Framework::new(event_store, DummySnapshotStore, ());
+        framework.register_aggregate::<TestAggregate>().unwrap();
+
+        let saga_store = Arc::new(InMemorySagaStore::<JsonCodec>::new());
+        framework.register_saga::<CountingSaga, _>(YieldingSagaStore(saga_store.clone()));
+
+        (framework, saga_store)
+    }
+
+    #[tokio::test]
+    async fn saga_failure_does_not_fail_committed_command() {
+        let (mut framework, saga_store) = framework();
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let reported = errors.clone();
+        framework.register_saga_error_handler(move |saga, error| {
+            reported.lock().unwrap().push((saga, error.to_string()));
+        });
+
+        let listened = Arc::new(Mutex::new(0));
+        let listener = listened.clone();
+        framework.register_event_callback::<TestAggregate, _>(2, move |_| {
+            *listener.lock().unwrap() += 1;
+            Ok(())
+        });
+
+        let position = framework
+            .command(TestCommand {
+                aggregate_id: 1,
+                event: TestEvent::Renamed(String::from("a")),
+            })
+            .await
+            .unwrap();
+        assert_eq!(position.version, 1);
+
+        assert_eq!(*listened.lock().unwrap(), 1);
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![(
+                core::any::type_name::<CountingSaga>(),
+                String::from("Command rejected: Nothing to remove"),
+            )]
+        );
+
+        let (version, saga) = saga_store.read::<CountingSaga>(1).await.unwrap().unwrap();
+        assert_eq!((version, saga.events), (1, 1));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_saga_updates_are_not_lost() {
+        let (framework, saga_store) = framework();
+        let framework = Arc::new(framework);
+
+        let tasks = (0..32)
+            .map(|aggregate_id| {
+                let framework = framework.clone();
+                tokio::spawn(async move {
+                    framework
+                        .command(TestCommand {
+                            aggregate_id,
+                            event: TestEvent::Added(1),
+                        })
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let (version, saga) = saga_store.read::<CountingSaga>(1).await.unwrap().unwrap();
+        assert_eq!((version, saga.events), (32, 32));
+    }
+
+    #[tokio::test]
+    async fn failing_saga_timeout_does_not_stop_the_others() {
+        let event_store = SqliteDatabase::open_in_memory()
+            .unwrap()
+            .event_store::<JsonCodec>();
+        let mut framework = Framework::new(event_store, DummySnapshotStore, ());
+        framework.register_aggregate::<TestAggregate>().unwrap();
+
+        let saga_store = Arc::new(InMemorySagaStore::<JsonCodec>::new());
+        framework.register_saga::<TimingSaga<true>, _>(saga_store.clone());
+        framework.register_saga::<TimingSaga<false>, _>(saga_store.clone());
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let reported = errors.clone();
+        framework.register_saga_error_handler(move |saga, error| {
+            reported.lock().unwrap().push((saga, error.to_string()));
+        });
+
+        framework
+            .command(TestCommand {
+                aggregate_id: 1,
+                event: TestEvent::Added(1),
+            })
+            .await
+            .unwrap();
+        framework.handle_saga_timeouts(1).await;
+
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![(
+                core::any::type_name::<TimingSaga<true>>(),
+                String::from("Command rejected: timed out"),
+            )]
+        );
+        let (_, saga) = saga_store
+            .read::<TimingSaga<false>>(1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(saga.timed_out);
+    }
+
+    // events of an aggregate other than `TestAggregate`, its read model is never updated here
+    struct OtherEvent;
+
+    impl Event for OtherEvent {
+        fn type_id(&self) -> EventTypeId {
+            1
+        }
+
+        fn type_name(&self) -> &'static str {
+            "OtherEvent"
+        }
+
+        fn event_types() -> &'static [EventType] {
+            &[EventType {
+                id: 1,
+                name: "OtherEvent",
+                revision: 1,
+            }]
+        }
+    }
+
+    #[derive(Default, Clone)]
+    struct OtherReadModel;
+
+    impl ReadModel for OtherReadModel {
+        type Event = OtherEvent;
+
+        fn apply_event(&mut self, _event: &OtherEvent) -> Result<ReadModelUpdate> {
+            Ok(ReadModelUpdate::Upsert)
+        }
+    }
+
+    type TotalStores = (
+        InMemoryReadModelStore<TestReadModel>,
+        InMemoryReadModelStore<OtherReadModel>,
+    );
+
+    struct TotalQuery(u64);
+
+    impl Query for TotalQuery {
+        type Handler = TotalQueryHandler;
+    }
+
+    struct TotalQueryHandler;
+
+    impl QueryHandler<TotalQuery> for TotalQueryHandler {
+        type ReadModelStore = TotalStores;
+        type Output = Option<u64>;
+
+        async fn handle(
+            stores: ReadModelStoreRef<'_, TotalStores>,
+            query: TotalQuery,
+        ) -> Result<Option<u64>> {
+            Ok(stores.0.read(query.0).await?.map(|x| x.total))
+        }
+    }
+
+    #[tokio::test]
+    async fn query_at_least_only_waits_for_stores_of_the_aggregate() {
+        let event_store = SqliteDatabase::open_in_memory()
+            .unwrap()
+            .event_store::<JsonCodec>();
+        let mut framework = Framework::new(event_store, DummySnapshotStore, TotalStores::default());
+        framework.register_aggregate::<TestAggregate>().unwrap();
+
+        let position = framework
+            .command(TestCommand {
+                aggregate_id: 5,
+                event: TestEvent::Added(3),
+            })
+            .await
+            .unwrap();
+        assert_eq!(position.aggregate_type_id, TestAggregate::type_id());
+
+        let total = framework
+            .query_at_least(TotalQuery(5), Some(position))
+            .await
+            .unwrap();
+        assert_eq!(total, Some(3));
+
+        let ahead = Position {
+            version: position.version + 1,
+            ..position
+        };
+        assert!(matches!(
+            framework.query_at_least(TotalQuery(5), Some(ahead)).await,
+            Err(FrameworkError::ReadModelBehind(2, 1))
+        ));
+
+        let unknown = Position {
+            aggregate_type_id: TestAggregate::type_id() + 1,
+            ..position
+        };
+        assert!(matches!(
+            framework.query_at_least(TotalQuery(5), Some(unknown)).await,
+            Err(FrameworkError::UnknownAggregateType(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn cached_aggregate_sees_events_of_other_writers() {
+        let database = SqliteDatabase::open_in_memory().unwrap();
+        let mut cached =
+            Framework::new(database.event_store::<JsonCodec>(), DummySnapshotStore, ());
+        cached.register_aggregate::<TestAggregate>().unwrap();
+        cached.enable_aggregate_cache(8);
+        let mut other = Framework::new(database.event_store::<JsonCodec>(), DummySnapshotStore, ());
+        other.register_aggregate::<TestAggregate>().unwrap();
+
+        // cached with nothing to remove
+        cached
+            .command(TestCommand {
+                aggregate_id: 1,
+                event: TestEvent::Renamed(String::from("a")),
+            })
+            .await
+            .unwrap();
+        other
+            .command(TestCommand {
+                aggregate_id: 1,
+                event: TestEvent::Added(1),
+            })
+            .await
+            .unwrap();
+
+        // rejected if the cached aggregate missed the addition
+        let position = cached
+            .command(TestCommand {
+                aggregate_id: 1,
+                event: TestEvent::Removed,
+            })
+            .await
+            .unwrap();
+        assert_eq!(position.version, 3);
+    }
+
+    // lets other commands read the stream before this one saves
+    struct YieldingEventStore(SqliteEventStore<JsonCodec>);
+
+    impl EventStore for YieldingEventStore {
+        async fn read<A>(
+            &self,
+            aggregate_id: u64,
+            from_version: u32,
+        ) -> Result<Vec<VersionedEvent<A::Event>>>
+        where
+            A: Aggregate,
+        {
+            let events = self.0.read::<A>(aggregate_id, from_version).await;
+            tokio::task::yield_now().await;
+            events
+        }
+
+        async fn save<A>(
+            &self,
+            aggregate_id: u64,
+            expected_version: u32,
+            events: &[A::Event],
+        ) -> Result<()>
+        where
+            A: Aggregate,
+        {
+            self.0
+                .save::<A>(aggregate_id, expected_version, events)
+                .await
+        }
+
+        async fn tombstone<A>(&self, aggregate_id: u64) -> Result<()>
+        where
+            A: Aggregate,
+        {
+            self.0.tombstone::<A>(aggregate_id).await
+        }
+
+        async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
+        where
+            A: Aggregate,
+        {
+            self.0.shred::<A>(aggregate_id).await
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn sequential_commands_do_not_conflict() {
+        let event_store = SqliteDatabase::open_in_memory()
+            .unwrap()
+            .event_store::<JsonCodec>();
+        let mut framework = Framework::new(YieldingEventStore(event_store), DummySnapshotStore, ());
+        framework.register_aggregate::<TestAggregate>().unwrap();
+        framework.enable_sequential_commands(4);
+        let framework = Arc::new(framework);
+
+        let tasks = (0..32)
+            .map(|_| {
+                let framework = framework.clone();
+                tokio::spawn(async move {
+                    framework
+                        .command(TestCommand {
+                            aggregate_id: 1,
+                            event: TestEvent::Added(1),
+                        })
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut versions = Vec::new();
+        for task in tasks {
+            versions.push(task.await.unwrap().unwrap().version);
+        }
+
+        versions.sort();
+        assert_eq!(versions, (1..=32).collect::<Vec<_>>());
+    }
+
+    // flips the payload's bits, refusing aggregates whose key was destroyed
+    #[derive(Default)]
+    struct ShreddingCipher {
+        shredded: Mutex<Vec<u64>>,
+    }
+
+    impl ShreddingCipher {
+        fn check(&self, aggregate_id: u64) -> Result<()> {
+            if self.shredded.lock().unwrap().contains(&aggregate_id) {
+                return Err(FrameworkError::AggregateShredded(aggregate_id));
+            }
+
+            Ok(())
+        }
+    }
+
+    impl Cipher for ShreddingCipher {
+        async fn encrypt<A>(&self, aggregate_id: u64, data: Vec<u8>) -> Result<Vec<u8>>
+        where
+            A: Aggregate,
+        {
+            self.check(aggregate_id)?;
+            Ok(data.into_iter().map(|x| !x).collect())
+        }
+
+        async fn decrypt<A>(&self, aggregate_id: u64, data: Vec<u8>) -> Result<Vec<u8>>
+        where
+            A: Aggregate,
+        {
+            self.check(aggregate_id)?;
+            Ok(data.into_iter().map(|x| !x).collect())
+        }
+
+        async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
+        where
+            A: Aggregate,
+        {
+            self.shredded.lock().unwrap().push(aggregate_id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn shred_purges_read_models_and_payloads() {
+        let event_store = SqliteDatabase::open_in_memory()
+            .unwrap()
+            .event_store::<JsonCodec>()
+            .with_cipher(ShreddingCipher::default());
+        let mut framework = Framework::new(event_store, DummySnapshotStore, TotalStores::default());
+        framework.register_aggregate::<TestAggregate>().unwrap();
+
+        for aggregate_id in [1, 2] {
+            framework
+                .command(TestCommand {
+                    aggregate_id,
+                    event: TestEvent::Added(3),
+                })
+                .await
+                .unwrap();
+        }
+
+        framework.shred::<TestAggregate>(1).await.unwrap();
+
+        assert_eq!(framework.query(TotalQuery(1)).await.unwrap(), None);
+        assert_eq!(framework.query(TotalQuery(2)).await.unwrap(), Some(3));
+
+        assert!(matches!(
+            framework
+                .command(TestCommand {
+                    aggregate_id: 1,
+                    event: TestEvent::Added(1),
+                })
+                .await,
+            Err(CommandError::Framework(FrameworkError::AggregateShredded(
+                1
+            )))
+        ));
+        framework
+            .command(TestCommand {
+                aggregate_id: 2,
+                event: TestEvent::Added(1),
+            })
+            .await
+            .unwrap();
     }
 }
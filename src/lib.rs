@@ -1,27 +1,80 @@
 #![no_std]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 mod aggregate;
 mod as_any;
+#[cfg(feature = "std")]
+mod cache;
+mod cipher;
+mod codec;
 mod command;
+mod command_bus;
 mod error;
 mod event;
 mod event_listener;
+#[cfg(feature = "std")]
+mod file;
 mod framework;
+#[cfg(feature = "std")]
+mod mailbox;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod query;
+mod query_bus;
 mod read_model;
+mod registry;
 mod repository;
+mod saga;
+#[cfg(feature = "sled")]
+mod sled;
 mod snapshot;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod telemetry;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use self::{
     aggregate::{Aggregate, AggregateTypeId},
-    command::Command,
-    error::FrameworkError,
-    event::{Event, EventStore, EventTypeId},
+    cipher::{Cipher, NoCipher},
+    codec::{Codec, CodecId},
+    command::{Command, ExpectedState, Position},
+    command_bus::CommandBus,
+    error::{CommandError, FrameworkError},
+    event::{
+        type_id_from_name, Event, EventStore, EventType, EventTypeId, GlobalEvent, VersionedEvent,
+    },
     framework::Framework,
-    query::{Query, QueryHandler},
-    read_model::{ReadModel, ReadModelStore},
+    query::{Query, QueryHandler, QueryStores, ReadModelStoreRef},
+    query_bus::QueryBus,
+    read_model::{Page, ReadModel, ReadModelScan, ReadModelStore, ReadModelUpdate},
+    registry::{AggregateType, EventRegistry},
+    saga::{Saga, SagaStore, SagaTypeId},
     snapshot::{DummySnapshotStore, SnapshotStore},
 };
 
+#[cfg(feature = "cbor")]
+pub use self::codec::CborCodec;
+#[cfg(feature = "json")]
+pub use self::codec::JsonCodec;
+#[cfg(feature = "postcard")]
+pub use self::codec::PostcardCodec;
+#[cfg(feature = "std")]
+pub use self::file::{FileEventStore, FileSnapshotStore};
+#[cfg(feature = "postgres")]
+pub use self::postgres::{PostgresEventStore, PostgresSubscription};
+#[cfg(feature = "sled")]
+pub use self::sled::{SledDatabase, SledEventStore, SledReadModelStore, SledSnapshotStore};
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::{
+    SqliteDatabase, SqliteEventStore, SqliteReadModelStore, SqliteSnapshotStore,
+};
+#[cfg(feature = "derive")]
+pub use framework_derive::{aggregate, Command, Event};
+
 pub type Result<T> = core::result::Result<T, FrameworkError>;
+
+pub(crate) type BoxFuture<'a, T> =
+    core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = T> + Send + 'a>>;
@@ -0,0 +1,364 @@
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use core::{
+    any::Any,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::aggregate::{Aggregate, AggregateTypeId};
+
+type CachedAggregate = Box<dyn Any + Send + Sync>;
+
+type Key = (AggregateTypeId, u64);
+
+struct Entry {
+    used: u64,
+    mailbox: Arc<Mailbox>,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: BTreeMap<Key, Entry>,
+    // keys by last use, oldest first
+    lru: BTreeMap<u64, Key>,
+    next_use: u64,
+}
+
+// Queues commands per aggregate so they run one at a time, in arrival order. The aggregate loaded
+// by a command is kept in its mailbox for the next one, skipping the replay. Beyond `capacity`
+// the least recently used idle mailbox is dropped, like `AggregateCache` entries.
+pub(crate) struct Mailboxes {
+    capacity: usize,
+    mailboxes: Mutex<Entries>,
+}
+
+impl Mailboxes {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            mailboxes: Mutex::new(Entries::default()),
+        }
+    }
+
+    // waits for the commands queued before this one
+    pub(crate) fn lock<A>(&self, aggregate_id: u64) -> Acquire
+    where
+        A: Aggregate,
+    {
+        let mut mailboxes = self
+            .mailboxes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mailboxes = &mut *mailboxes;
+
+        let key = (A::type_id(), aggregate_id);
+        let used = mailboxes.next_use;
+        mailboxes.next_use += 1;
+
+        let mailbox = match mailboxes.entries.get_mut(&key) {
+            Some(entry) => {
+                mailboxes.lru.remove(&entry.used);
+                entry.used = used;
+                entry.mailbox.clone()
+            }
+            None => {
+                if mailboxes.entries.len() >= self.capacity {
+                    // mailboxes with commands queued can't be dropped, they're skipped
+                    let idle = mailboxes.lru.iter().find_map(|(used, key)| {
+                        let entry = &mailboxes.entries[key];
+                        (Arc::strong_count(&entry.mailbox) == 1).then_some((*used, *key))
+                    });
+                    if let Some((used, key)) = idle {
+                        mailboxes.lru.remove(&used);
+                        mailboxes.entries.remove(&key);
+                    }
+                }
+
+                let mailbox = Arc::new(Mailbox::default());
+                mailboxes.entries.insert(
+                    key,
+                    Entry {
+                        used,
+                        mailbox: mailbox.clone(),
+                    },
+                );
+                mailbox
+            }
+        };
+        mailboxes.lru.insert(used, key);
+
+        Acquire {
+            mailbox,
+            ticket: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Mailbox {
+    state: Mutex<MailboxState>,
+}
+
+impl Mailbox {
+    fn state(&self) -> MutexGuard<'_, MailboxState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Default)]
+struct MailboxState {
+    next_ticket: u64,
+    serving: u64,
+    waiting: BTreeMap<u64, Waker>,
+    // tickets dropped before being served
+    abandoned: BTreeSet<u64>,
+    cached: Option<CachedAggregate>,
+}
+
+impl MailboxState {
+    fn advance(&mut self) {
+        self.serving += 1;
+        while self.abandoned.remove(&self.serving) {
+            self.serving += 1;
+        }
+
+        if let Some(waker) = self.waiting.remove(&self.serving) {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct Acquire {
+    mailbox: Arc<Mailbox>,
+    ticket: Option<u64>,
+}
+
+impl Future for Acquire {
+    type Output = MailboxGuard;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<MailboxGuard> {
+        let mut state = self.mailbox.state();
+
+        let ticket = match self.ticket {
+            Some(ticket) => ticket,
+            None => {
+                let ticket = state.next_ticket;
+                state.next_ticket += 1;
+                ticket
+            }
+        };
+
+        if state.serving != ticket {
+            state.waiting.insert(ticket, cx.waker().clone());
+            drop(state);
+            self.ticket = Some(ticket);
+            return Poll::Pending;
+        }
+
+        let cached = state.cached.take();
+        drop(state);
+        self.ticket = None;
+
+        Poll::Ready(MailboxGuard {
+            mailbox: self.mailbox.clone(),
+            cached,
+        })
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+
+        let mut state = self.mailbox.state();
+        state.waiting.remove(&ticket);
+        if state.serving == ticket {
+            state.advance();
+        } else {
+            state.abandoned.insert(ticket);
+        }
+    }
+}
+
+// the aggregate's turn, the next command is let in when dropped
+pub(crate) struct MailboxGuard {
+    mailbox: Arc<Mailbox>,
+    cached: Option<CachedAggregate>,
+}
+
+impl MailboxGuard {
+    pub(crate) fn take<A>(&mut self) -> Option<(u32, A)>
+    where
+        A: Aggregate + 'static,
+    {
+        self.cached
+            .take()
+            .and_then(|cached| cached.downcast::<(u32, A)>().ok())
+            .map(|cached| *cached)
+    }
+
+    pub(crate) fn put<A>(&mut self, aggregate: Option<(u32, A)>)
+    where
+        A: Aggregate + 'static,
+    {
+        self.cached = aggregate.map(|aggregate| Box::new(aggregate) as CachedAggregate);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.cached = None;
+    }
+}
+
+impl Drop for MailboxGuard {
+    fn drop(&mut self) {
+        let mut state = self.mailbox.state();
+        state.cached = self.cached.take();
+        state.advance();
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use alloc::vec::Vec;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use std::task::Wake;
+
+    use super::*;
+    use crate::testing::TestAggregate;
+
+    #[derive(Default)]
+    struct Flag(AtomicBool);
+
+    impl Flag {
+        fn woken(&self) -> bool {
+            self.0.swap(false, Ordering::SeqCst)
+        }
+    }
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // an `Acquire` polled with its own waker
+    struct Waiter {
+        acquire: Acquire,
+        flag: Arc<Flag>,
+    }
+
+    impl Waiter {
+        fn new(mailboxes: &Mailboxes, aggregate_id: u64) -> Self {
+            Self {
+                acquire: mailboxes.lock::<TestAggregate>(aggregate_id),
+                flag: Arc::new(Flag::default()),
+            }
+        }
+
+        fn poll(&mut self) -> Option<MailboxGuard> {
+            let waker = Waker::from(self.flag.clone());
+            match Pin::new(&mut self.acquire).poll(&mut Context::from_waker(&waker)) {
+                Poll::Ready(guard) => Some(guard),
+                Poll::Pending => None,
+            }
+        }
+    }
+
+    #[test]
+    fn acquires_are_served_in_order() {
+        let mailboxes = Mailboxes::new(8);
+        let mut waiters = (0..3)
+            .map(|_| Waiter::new(&mailboxes, 1))
+            .collect::<Vec<_>>();
+
+        let mut first = waiters[0].poll().unwrap();
+        assert!(waiters[1].poll().is_none());
+        assert!(waiters[2].poll().is_none());
+
+        first.put(Some((1, TestAggregate::default())));
+        drop(first);
+        assert!(waiters[1].flag.woken());
+        assert!(!waiters[2].flag.woken());
+        assert!(
+            waiters[2].poll().is_none(),
+            "the third must wait for the second"
+        );
+
+        let mut second = waiters[1].poll().unwrap();
+        assert!(second.take::<TestAggregate>().is_some());
+        drop(second);
+        assert!(waiters[2].flag.woken());
+        assert!(waiters[2].poll().is_some());
+    }
+
+    #[test]
+    fn dropped_waiting_acquire_is_skipped() {
+        let mailboxes = Mailboxes::new(8);
+        let mut waiters = (0..3)
+            .map(|_| Waiter::new(&mailboxes, 1))
+            .collect::<Vec<_>>();
+
+        let first = waiters[0].poll().unwrap();
+        assert!(waiters[1].poll().is_none());
+        assert!(waiters[2].poll().is_none());
+
+        let mut third = waiters.pop().unwrap();
+        waiters.pop();
+        drop(first);
+
+        assert!(third.flag.woken());
+        assert!(third.poll().is_some());
+    }
+
+    #[test]
+    fn dropped_woken_acquire_passes_the_turn_on() {
+        let mailboxes = Mailboxes::new(8);
+        let mut waiters = (0..3)
+            .map(|_| Waiter::new(&mailboxes, 1))
+            .collect::<Vec<_>>();
+
+        let first = waiters[0].poll().unwrap();
+        assert!(waiters[1].poll().is_none());
+        assert!(waiters[2].poll().is_none());
+
+        let mut third = waiters.pop().unwrap();
+        drop(first);
+        assert!(waiters[1].flag.woken());
+
+        // woken, but dropped before it got to poll
+        waiters.pop();
+        assert!(third.flag.woken());
+        assert!(third.poll().is_some());
+    }
+
+    fn kept(mailboxes: &Mailboxes) -> Vec<u64> {
+        let mailboxes = mailboxes.mailboxes.lock().unwrap();
+        mailboxes.entries.keys().map(|(_, id)| *id).collect()
+    }
+
+    #[test]
+    fn least_recently_used_idle_mailbox_is_dropped() {
+        let mailboxes = Mailboxes::new(2);
+        drop(mailboxes.lock::<TestAggregate>(1));
+        drop(mailboxes.lock::<TestAggregate>(2));
+        drop(mailboxes.lock::<TestAggregate>(1));
+
+        drop(mailboxes.lock::<TestAggregate>(3));
+        assert_eq!(kept(&mailboxes), [1, 3]);
+
+        // 1 is the oldest, but busy
+        let mut busy = Waiter::new(&mailboxes, 1);
+        let _guard = busy.poll().unwrap();
+        drop(mailboxes.lock::<TestAggregate>(3));
+        drop(mailboxes.lock::<TestAggregate>(4));
+        assert_eq!(kept(&mailboxes), [1, 4]);
+    }
+}
@@ -0,0 +1,610 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    future::poll_fn,
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use tokio::sync::{watch, Mutex};
+use tokio_postgres::{
+    error::SqlState,
+    tls::{MakeTlsConnect, TlsConnect},
+    AsyncMessage, Client, Config, Socket,
+};
+
+use crate::{
+    aggregate::{Aggregate, AggregateTypeId},
+    cipher::{Cipher, NoCipher},
+    codec::Codec,
+    error::FrameworkError,
+    event::{EventStore, GlobalEvent, VersionedEvent},
+    telemetry::gauge,
+    Result,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        position BIGSERIAL PRIMARY KEY,
+        aggregate_type BIGINT NOT NULL,
+        aggregate_id BIGINT NOT NULL,
+        version BIGINT NOT NULL,
+        codec SMALLINT NOT NULL,
+        data BYTEA NOT NULL,
+        UNIQUE (aggregate_type, aggregate_id, version)
+    );
+    CREATE INDEX IF NOT EXISTS events_by_type ON events (aggregate_type, position);
+    CREATE TABLE IF NOT EXISTS tombstones (
+        aggregate_type BIGINT NOT NULL,
+        aggregate_id BIGINT NOT NULL,
+        PRIMARY KEY (aggregate_type, aggregate_id)
+    );
+";
+
+// advisory lock key taken by every append
+const APPEND_LOCK: i64 = 0x6576_656e_7473;
+
+const SUBSCRIPTION_BATCH: usize = 256;
+
+const READERS: usize = 4;
+
+fn database_error(e: tokio_postgres::Error) -> FrameworkError {
+    match e.code() {
+        Some(&SqlState::UNIQUE_VIOLATION) => FrameworkError::ConcurrencyError,
+        _ => FrameworkError::DatabaseError(e.to_string()),
+    }
+}
+
+pub struct PostgresEventStore<C, K = NoCipher>
+where
+    C: Codec,
+{
+    // appends and tombstones need a transaction, they take turns on this client
+    writer: Arc<Mutex<Client>>,
+    // reads are spread over these and pipelined, they never wait for a transaction
+    readers: Arc<[Client]>,
+    next_reader: Arc<AtomicUsize>,
+    // global position of the last append notified on the `events` channel
+    notifications: watch::Receiver<u64>,
+    cipher: Arc<K>,
+    _codec: PhantomData<C>,
+}
+
+impl<C, K> Clone for PostgresEventStore<C, K>
+where
+    C: Codec,
+{
+    fn clone(&self) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            readers: self.readers.clone(),
+            next_reader: self.next_reader.clone(),
+            notifications: self.notifications.clone(),
+            cipher: self.cipher.clone(),
+            _codec: PhantomData,
+        }
+    }
+}
+
+impl<C> PostgresEventStore<C>
+where
+    C: Codec,
+{
+    // must be called within a tokio runtime, connections are driven by spawned tasks, `tls` is
+    // `tokio_postgres::NoTls` or a connector such as `postgres-native-tls` or `postgres-openssl`
+    pub async fn connect<T>(config: &str, tls: T) -> Result<Self>
+    where
+        T: MakeTlsConnect<Socket> + Clone,
+        T::Stream: Send + 'static,
+        T::TlsConnect: Send,
+        <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    {
+        Self::connect_with_readers(config, tls, READERS).await
+    }
+
+    // opens a connection for appends and `readers` connections for reads, at least one
+    pub async fn connect_with_readers<T>(config: &str, tls: T, readers: usize) -> Result<Self>
+    where
+        T: MakeTlsConnect<Socket> + Clone,
+        T::Stream: Send + 'static,
+        T::TlsConnect: Send,
+        <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    {
+        let config = config.parse::<Config>().map_err(database_error)?;
+
+        let (writer, connection) = config.connect(tls.clone()).await.map_err(database_error)?;
+        tokio::spawn(connection);
+        writer.batch_execute(SCHEMA).await.map_err(database_error)?;
+
+        // the first reader listens for appends
+        let (listener, mut connection) =
+            config.connect(tls.clone()).await.map_err(database_error)?;
+        let (sender, notifications) = watch::channel(0);
+        tokio::spawn(async move {
+            while let Some(message) = poll_fn(|cx| connection.poll_message(cx)).await {
+                match message {
+                    Ok(AsyncMessage::Notification(notification)) => {
+                        if let Ok(position) = notification.payload().parse() {
+                            sender.send_replace(position);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+        listener
+            .batch_execute("LISTEN events")
+            .await
+            .map_err(database_error)?;
+
+        let mut clients = Vec::with_capacity(readers.max(1));
+        clients.push(listener);
+        while clients.len() < readers {
+            let (client, connection) = config.connect(tls.clone()).await.map_err(database_error)?;
+            tokio::spawn(connection);
+            clients.push(client);
+        }
+
+        Ok(Self {
+            writer: Arc::new(Mutex::new(writer)),
+            readers: clients.into(),
+            next_reader: Arc::new(AtomicUsize::new(0)),
+            notifications,
+            cipher: Arc::new(NoCipher),
+            _codec: PhantomData,
+        })
+    }
+
+    // event payloads are encrypted with `cipher` before they're stored
+    pub fn with_cipher<K>(self, cipher: K) -> PostgresEventStore<C, K>
+    where
+        K: Cipher,
+    {
+        PostgresEventStore {
+            writer: self.writer,
+            readers: self.readers,
+            next_reader: self.next_reader,
+            notifications: self.notifications,
+            cipher: Arc::new(cipher),
+            _codec: PhantomData,
+        }
+    }
+}
+
+impl<C, K> PostgresEventStore<C, K>
+where
+    C: Codec,
+    K: Cipher,
+{
+    fn reader(&self) -> &Client {
+        let next = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        &self.readers[next % self.readers.len()]
+    }
+
+    // returns up to `limit` events of `A` with a global position greater than `after`
+    pub async fn read_all<A>(&self, after: u64, limit: usize) -> Result<Vec<GlobalEvent<A::Event>>>
+    where
+        A: Aggregate,
+    {
+        Ok(self.read_batch::<A>(after, limit).await?.1)
+    }
+
+    // also returns the position of the last row read, which is past the last event when events of
+    // shredded aggregates were skipped
+    async fn read_batch<A>(
+        &self,
+        after: u64,
+        limit: usize,
+    ) -> Result<(Option<u64>, Vec<GlobalEvent<A::Event>>)>
+    where
+        A: Aggregate,
+    {
+        let rows = self
+            .reader()
+            .query(
+                "SELECT position, aggregate_id, version, codec, data FROM events
+                 WHERE aggregate_type = $1 AND position > $2
+                 ORDER BY position LIMIT $3",
+                &[&(A::type_id() as i64), &(after as i64), &(limit as i64)],
+            )
+            .await
+            .map_err(database_error)?;
+
+        let last = rows.last().map(|row| row.get::<_, i64>(0) as u64);
+
+        let mut events = Vec::with_capacity(rows.len());
+        for row in rows {
+            let aggregate_id = row.get::<_, i64>(1) as u64;
+            let data = match self.cipher.decrypt::<A>(aggregate_id, row.get(4)).await {
+                Ok(data) => data,
+                // shredded aggregates are skipped rather than stalling the subscription
+                Err(FrameworkError::AggregateShredded(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            events.push(GlobalEvent {
+                position: row.get::<_, i64>(0) as u64,
+                aggregate_id,
+                version: row.get::<_, i64>(2) as u32,
+                event: C::decode_tagged(row.get::<_, i16>(3) as u8, &data)?,
+            });
+        }
+
+        Ok((last, events))
+    }
+
+    // catch-up subscription to events of `A` after the global position `after`
+    pub fn subscribe<A>(&self, after: u64) -> PostgresSubscription<A, C, K>
+    where
+        A: Aggregate,
+    {
+        PostgresSubscription {
+            store: self.clone(),
+            position: after,
+            _aggregate: PhantomData,
+        }
+    }
+
+    async fn is_tombstoned(
+        client: &impl tokio_postgres::GenericClient,
+        aggregate_type: AggregateTypeId,
+        aggregate_id: u64,
+    ) -> core::result::Result<bool, tokio_postgres::Error> {
+        client
+            .query_opt(
+                "SELECT 1 FROM tombstones WHERE aggregate_type = $1 AND aggregate_id = $2",
+                &[&(aggregate_type as i64), &(aggregate_id as i64)],
+            )
+            .await
+            .map(|x| x.is_some())
+    }
+
+    async fn append(
+        client: &mut Client,
+        aggregate_type: AggregateTypeId,
+        aggregate_id: u64,
+        expected_version: u32,
+        events: &[Vec<u8>],
+    ) -> core::result::Result<Result<()>, tokio_postgres::Error> {
+        let transaction = client.transaction().await?;
+
+        // appends are serialized so positions become visible in order, subscribers rely on this
+        // to never skip over an event committed after a later position
+        transaction
+            .execute("SELECT pg_advisory_xact_lock($1)", &[&APPEND_LOCK])
+            .await?;
+
+        if Self::is_tombstoned(&transaction, aggregate_type, aggregate_id).await? {
+            return Ok(Err(FrameworkError::AggregateDeleted(aggregate_id)));
+        }
+
+        let version: i64 = transaction
+            .query_one(
+                "SELECT COALESCE(MAX(version), 0) FROM events
+                 WHERE aggregate_type = $1 AND aggregate_id = $2",
+                &[&(aggregate_type as i64), &(aggregate_id as i64)],
+            )
+            .await?
+            .get(0);
+        if version != expected_version as i64 {
+            return Ok(Err(FrameworkError::ConcurrencyError));
+        }
+
+        let statement = transaction
+            .prepare(
+                "INSERT INTO events (aggregate_type, aggregate_id, version, codec, data)
+                 VALUES ($1, $2, $3, $4, $5) RETURNING position",
+            )
+            .await?;
+
+        let mut position = None;
+        for (version, data) in (expected_version as i64 + 1..).zip(events) {
+            let row = transaction
+                .query_one(
+                    &statement,
+                    &[
+                        &(aggregate_type as i64),
+                        &(aggregate_id as i64),
+                        &version,
+                        &(C::ID as i16),
+                        data,
+                    ],
+                )
+                .await?;
+            position = Some(row.get::<_, i64>(0));
+        }
+
+        // delivered to listeners once the transaction commits
+        if let Some(position) = position {
+            transaction
+                .execute("SELECT pg_notify('events', $1)", &[&position.to_string()])
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(Ok(()))
+    }
+}
+
+impl<C, K> EventStore for PostgresEventStore<C, K>
+where
+    C: Codec + Sync + Send,
+    K: Cipher + 'static,
+{
+    async fn read<A>(
+        &self,
+        aggregate_id: u64,
+        from_version: u32,
+    ) -> Result<Vec<VersionedEvent<A::Event>>>
+    where
+        A: Aggregate,
+    {
+        let client = self.reader();
+
+        let rows = client
+            .query(
+                "SELECT version, codec, data FROM events
+                 WHERE aggregate_type = $1 AND aggregate_id = $2 AND version > $3
+                 ORDER BY version",
+                &[
+                    &(A::type_id() as i64),
+                    &(aggregate_id as i64),
+                    &(from_version as i64),
+                ],
+            )
+            .await
+            .map_err(database_error)?;
+
+        // checked after reading, a tombstone committed in between removed the events read
+        if Self::is_tombstoned(client, A::type_id(), aggregate_id)
+            .await
+            .map_err(database_error)?
+        {
+            return Err(FrameworkError::AggregateDeleted(aggregate_id));
+        }
+
+        let mut events = Vec::with_capacity(rows.len());
+        for row in rows {
+            let data = self.cipher.decrypt::<A>(aggregate_id, row.get(2)).await?;
+            events.push(VersionedEvent {
+                version: row.get::<_, i64>(0) as u32,
+                event: C::decode_tagged(row.get::<_, i16>(1) as u8, &data)?,
+            });
+        }
+
+        Ok(events)
+    }
+
+    async fn save<A>(
+        &self,
+        aggregate_id: u64,
+        expected_version: u32,
+        events: &[A::Event],
+    ) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let mut encrypted = Vec::with_capacity(events.len());
+        for event in events {
+            let data = C::encode(event)?;
+            encrypted.push(self.cipher.encrypt::<A>(aggregate_id, data).await?);
+        }
+        let events = encrypted;
+
+        let mut client = self.writer.lock().await;
+
+        Self::append(
+            &mut client,
+            A::type_id(),
+            aggregate_id,
+            expected_version,
+            &events,
+        )
+        .await
+        .map_err(database_error)?
+    }
+
+    async fn tombstone<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let mut client = self.writer.lock().await;
+
+        let transaction = client.transaction().await.map_err(database_error)?;
+        transaction
+            .execute(
+                "INSERT INTO tombstones (aggregate_type, aggregate_id) VALUES ($1, $2)
+                 ON CONFLICT DO NOTHING",
+                &[&(A::type_id() as i64), &(aggregate_id as i64)],
+            )
+            .await
+            .map_err(database_error)?;
+        transaction
+            .execute(
+                "DELETE FROM events WHERE aggregate_type = $1 AND aggregate_id = $2",
+                &[&(A::type_id() as i64), &(aggregate_id as i64)],
+            )
+            .await
+            .map_err(database_error)?;
+        transaction.commit().await.map_err(database_error)
+    }
+
+    async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.cipher.shred::<A>(aggregate_id).await
+    }
+}
+
+pub struct PostgresSubscription<A, C, K = NoCipher>
+where
+    A: Aggregate,
+    C: Codec,
+{
+    store: PostgresEventStore<C, K>,
+    position: u64,
+    _aggregate: PhantomData<A>,
+}
+
+impl<A, C, K> PostgresSubscription<A, C, K>
+where
+    A: Aggregate,
+    C: Codec,
+    K: Cipher,
+{
+    // global position of the last event read
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    // returns the next batch of events, waiting for a notification once caught up
+    pub async fn next(&mut self) -> Result<Vec<GlobalEvent<A::Event>>> {
+        loop {
+            // marked seen before reading, so appends committed after the read still wake us up
+            let head = *self.store.notifications.borrow_and_update();
+
+            let (last, events) = self
+                .store
+                .read_batch::<A>(self.position, SUBSCRIPTION_BATCH)
+                .await?;
+            if let Some(last) = last {
+                self.position = last;
+            }
+
+            if !events.is_empty() {
+                // positions notified but not yet returned, across all aggregate types
+                gauge!(
+                    "framework_projection_lag",
+                    head.saturating_sub(self.position),
+                    "aggregate" => A::type_name(),
+                );
+
+                return Ok(events);
+            }
+            // a batch of shredded events only, there may be more to read
+            if last.is_some() {
+                continue;
+            }
+
+            self.store
+                .notifications
+                .changed()
+                .await
+                .map_err(|_| FrameworkError::DatabaseError(String::from("connection closed")))?;
+        }
+    }
+}
+
+// ignored by default, `cargo test -- --ignored` runs them against the database in
+// `POSTGRES_TEST_CONFIG`, e.g. "host=localhost user=postgres"
+#[cfg(all(test, feature = "testing", feature = "json"))]
+mod tests {
+    use alloc::{format, vec};
+    use core::time::Duration;
+    use std::env;
+
+    use tokio::time::timeout;
+    use tokio_postgres::NoTls;
+
+    use super::*;
+    use crate::{
+        codec::JsonCodec,
+        testing::{self, TestAggregate, TestEvent},
+    };
+
+    // each test gets a schema of its own, recreated on every run
+    async fn store(schema: &str) -> PostgresEventStore<JsonCodec> {
+        let config = env::var("POSTGRES_TEST_CONFIG").expect("POSTGRES_TEST_CONFIG is not set");
+
+        let (client, connection) = tokio_postgres::connect(&config, NoTls).await.unwrap();
+        tokio::spawn(connection);
+        client
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}",
+                schema
+            ))
+            .await
+            .unwrap();
+
+        let config = format!("{} options='-c search_path={}'", config, schema);
+        PostgresEventStore::connect(&config, NoTls).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs POSTGRES_TEST_CONFIG"]
+    async fn event_store_conformance() {
+        let store = store("framework_conformance").await;
+
+        testing::event_store_conformance(&store).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs POSTGRES_TEST_CONFIG"]
+    async fn reads_do_not_wait_for_appends() {
+        let store = store("framework_reads").await;
+        store
+            .save::<TestAggregate>(1, 0, &[TestEvent::Added(1)])
+            .await
+            .unwrap();
+
+        // as if an append were in progress
+        let _writer = store.writer.lock().await;
+
+        let events = timeout(Duration::from_secs(5), store.read::<TestAggregate>(1, 0))
+            .await
+            .expect("read waited for the writer")
+            .unwrap();
+        assert_eq!(events.len(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs POSTGRES_TEST_CONFIG"]
+    async fn subscription_catches_up_and_follows_appends() {
+        let store = store("framework_subscription").await;
+        store
+            .save::<TestAggregate>(1, 0, &[TestEvent::Added(1), TestEvent::Added(2)])
+            .await
+            .unwrap();
+        store
+            .save::<TestAggregate>(2, 0, &[TestEvent::Added(3)])
+            .await
+            .unwrap();
+
+        let mut subscription = store.subscribe::<TestAggregate>(0);
+        let events = subscription.next().await.unwrap();
+        let events = events
+            .into_iter()
+            .map(|e| (e.aggregate_id, e.version, e.event))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (1, 1, TestEvent::Added(1)),
+                (1, 2, TestEvent::Added(2)),
+                (2, 1, TestEvent::Added(3)),
+            ]
+        );
+
+        let writer = store.clone();
+        tokio::spawn(async move {
+            writer
+                .save::<TestAggregate>(1, 2, &[TestEvent::Removed])
+                .await
+                .unwrap();
+        });
+
+        let events = timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .expect("append wasn't notified")
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, TestEvent::Removed);
+        assert_eq!(subscription.position(), events[0].position);
+    }
+}
@@ -1,6 +1,10 @@
-use core::future::Future;
+use core::{any::TypeId, future::Future, ops::Deref};
 
-use crate::{read_model::ReadModelStore, Result};
+use crate::{
+    error::FrameworkError,
+    read_model::{ReadModelStore, ReadModelStores},
+    Result,
+};
 
 pub trait Query {
     type Handler: QueryHandler<Self>;
@@ -10,11 +14,166 @@ pub trait QueryHandler<Q>
 where
     Q: Query + ?Sized,
 {
-    type ReadModelStore: ReadModelStore + 'static;
+    type ReadModelStore: QueryStores + 'static;
     type Output;
 
     fn handle(
-        read_model_store: &Self::ReadModelStore,
+        read_model_store: ReadModelStoreRef<'_, Self::ReadModelStore>,
         query: Q,
     ) -> impl Future<Output = Result<Self::Output>> + Send;
 }
+
+pub trait QueryStores {
+    type Refs<'a>: Copy + Sync + Send
+    where
+        Self: 'a;
+
+    fn find<R>(read_model_stores: &R) -> Result<Self::Refs<'_>>
+    where
+        R: ReadModelStores;
+
+    // lowest position for the given id among the stores projecting events of type `event`, `None`
+    // if none of them does
+    fn position<'a>(
+        refs: Self::Refs<'a>,
+        event: TypeId,
+        id: u64,
+    ) -> impl Future<Output = Result<Option<u32>>> + Send + 'a
+    where
+        Self: 'a;
+}
+
+async fn store_position<S>(store: &S, event: TypeId, id: u64) -> Result<Option<u32>>
+where
+    S: ReadModelStore,
+{
+    if S::read_model_event_type() == event {
+        store.position(id).await.map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn lowest(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+pub struct ReadModelStoreRef<'a, S>(S::Refs<'a>)
+where
+    S: QueryStores + 'a;
+
+impl<'a, S> ReadModelStoreRef<'a, S>
+where
+    S: QueryStores + 'a,
+{
+    pub(crate) fn find<R>(read_model_stores: &'a R) -> Result<Self>
+    where
+        R: ReadModelStores,
+    {
+        Ok(Self(S::find(read_model_stores)?))
+    }
+
+    pub(crate) async fn position(&self, event: TypeId, id: u64) -> Result<Option<u32>> {
+        S::position(self.0, event, id).await
+    }
+}
+
+impl<'a, S> Deref for ReadModelStoreRef<'a, S>
+where
+    S: QueryStores + 'a,
+{
+    type Target = S::Refs<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S> QueryStores for S
+where
+    S: ReadModelStore + 'static,
+{
+    type Refs<'a> = &'a S;
+
+    fn find<R>(read_model_stores: &R) -> Result<&S>
+    where
+        R: ReadModelStores,
+    {
+        read_model_stores
+            .find::<S>()
+            .ok_or(FrameworkError::NoSuchReadModelStore)
+    }
+
+    async fn position<'a>(refs: &'a S, event: TypeId, id: u64) -> Result<Option<u32>>
+    where
+        Self: 'a,
+    {
+        store_position(refs, event, id).await
+    }
+}
+
+// TODO macro..
+impl<S1, S2> QueryStores for (S1, S2)
+where
+    S1: ReadModelStore + 'static,
+    S2: ReadModelStore + 'static,
+{
+    type Refs<'a> = (&'a S1, &'a S2);
+
+    fn find<R>(read_model_stores: &R) -> Result<(&S1, &S2)>
+    where
+        R: ReadModelStores,
+    {
+        Ok((S1::find(read_model_stores)?, S2::find(read_model_stores)?))
+    }
+
+    async fn position<'a>(refs: (&'a S1, &'a S2), event: TypeId, id: u64) -> Result<Option<u32>>
+    where
+        Self: 'a,
+    {
+        Ok(lowest(
+            store_position(refs.0, event, id).await?,
+            store_position(refs.1, event, id).await?,
+        ))
+    }
+}
+
+impl<S1, S2, S3> QueryStores for (S1, S2, S3)
+where
+    S1: ReadModelStore + 'static,
+    S2: ReadModelStore + 'static,
+    S3: ReadModelStore + 'static,
+{
+    type Refs<'a> = (&'a S1, &'a S2, &'a S3);
+
+    fn find<R>(read_model_stores: &R) -> Result<(&S1, &S2, &S3)>
+    where
+        R: ReadModelStores,
+    {
+        Ok((
+            S1::find(read_model_stores)?,
+            S2::find(read_model_stores)?,
+            S3::find(read_model_stores)?,
+        ))
+    }
+
+    async fn position<'a>(
+        refs: (&'a S1, &'a S2, &'a S3),
+        event: TypeId,
+        id: u64,
+    ) -> Result<Option<u32>>
+    where
+        Self: 'a,
+    {
+        Ok(lowest(
+            lowest(
+                store_position(refs.0, event, id).await?,
+                store_position(refs.1, event, id).await?,
+            ),
+            store_position(refs.2, event, id).await?,
+        ))
+    }
+}
@@ -0,0 +1,190 @@
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    codec::Codec,
+    error::FrameworkError,
+    event::EventStore,
+    framework::Framework,
+    query::{Query, QueryHandler},
+    read_model::ReadModelStores,
+    snapshot::SnapshotStore,
+    BoxFuture, Result,
+};
+
+type BoxedQueryHandler<F> =
+    Box<dyn for<'a> Fn(&'a F, &'a [u8]) -> BoxFuture<'a, Result<Vec<u8>>> + Sync + Send>;
+
+pub struct QueryBus<E, S, R, C>
+where
+    E: EventStore + 'static,
+    S: SnapshotStore + 'static,
+    R: ReadModelStores,
+    C: Codec,
+{
+    handlers: BTreeMap<String, BoxedQueryHandler<Framework<E, S, R>>>,
+    _phantom: PhantomData<C>,
+}
+
+impl<E, S, R, C> QueryBus<E, S, R, C>
+where
+    E: EventStore + Sync + 'static,
+    S: SnapshotStore + Sync + 'static,
+    R: ReadModelStores + Sync,
+    C: Codec,
+{
+    pub fn new() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn register<Q>(&mut self, name: &str)
+    where
+        Q: Query + DeserializeOwned + Send + 'static,
+        <Q::Handler as QueryHandler<Q>>::Output: Serialize,
+    {
+        self.handlers.insert(
+            name.to_string(),
+            Box::new(|framework, payload| {
+                Box::pin(async move {
+                    let query = C::decode::<Q>(payload)?;
+                    let output = framework.query(query).await?;
+
+                    C::encode(&output)
+                })
+            }),
+        );
+    }
+
+    pub async fn dispatch(
+        &self,
+        framework: &Framework<E, S, R>,
+        name: &str,
+        payload: &[u8],
+    ) -> Result<Vec<u8>> {
+        let Some(handler) = self.handlers.get(name) else {
+            return Err(FrameworkError::NoSuchQuery(name.to_string()));
+        };
+
+        handler(framework, payload).await
+    }
+}
+
+impl<E, S, R, C> Default for QueryBus<E, S, R, C>
+where
+    E: EventStore + Sync + 'static,
+    S: SnapshotStore + Sync + 'static,
+    R: ReadModelStores + Sync,
+    C: Codec,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "sqlite", feature = "json"))]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{
+        codec::JsonCodec,
+        query::ReadModelStoreRef,
+        read_model::ReadModelStore,
+        snapshot::DummySnapshotStore,
+        sqlite::{SqliteDatabase, SqliteEventStore},
+        testing::{InMemoryReadModelStore, TestCommand, TestEvent, TestReadModel},
+    };
+
+    type TestStores = (InMemoryReadModelStore<TestReadModel>,);
+    type TestFramework = Framework<SqliteEventStore<JsonCodec>, DummySnapshotStore, TestStores>;
+    type TestBus = QueryBus<SqliteEventStore<JsonCodec>, DummySnapshotStore, TestStores, JsonCodec>;
+
+    #[derive(Deserialize)]
+    struct TotalQuery {
+        aggregate_id: u64,
+    }
+
+    impl Query for TotalQuery {
+        type Handler = TotalQueryHandler;
+    }
+
+    struct TotalQueryHandler;
+
+    impl QueryHandler<TotalQuery> for TotalQueryHandler {
+        type ReadModelStore = InMemoryReadModelStore<TestReadModel>;
+        type Output = Option<u64>;
+
+        async fn handle(
+            store: ReadModelStoreRef<'_, Self::ReadModelStore>,
+            query: TotalQuery,
+        ) -> Result<Option<u64>> {
+            Ok(store.read(query.aggregate_id).await?.map(|x| x.total))
+        }
+    }
+
+    async fn setup() -> (TestFramework, TestBus) {
+        let event_store = SqliteDatabase::open_in_memory()
+            .unwrap()
+            .event_store::<JsonCodec>();
+        let framework = Framework::new(event_store, DummySnapshotStore, TestStores::default());
+        framework
+            .command(TestCommand {
+                aggregate_id: 1,
+                event: TestEvent::Added(3),
+            })
+            .await
+            .unwrap();
+
+        let mut bus = TestBus::new();
+        bus.register::<TotalQuery>("total");
+
+        (framework, bus)
+    }
+
+    #[tokio::test]
+    async fn dispatches_decoded_queries_and_encodes_their_output() {
+        let (framework, bus) = setup().await;
+
+        let output = bus
+            .dispatch(&framework, "total", b"{\"aggregate_id\": 1}")
+            .await
+            .unwrap();
+        assert_eq!(JsonCodec::decode::<Option<u64>>(&output).unwrap(), Some(3));
+
+        let output = bus
+            .dispatch(&framework, "total", b"{\"aggregate_id\": 2}")
+            .await
+            .unwrap();
+        assert_eq!(JsonCodec::decode::<Option<u64>>(&output).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn unknown_names_are_refused() {
+        let (framework, bus) = setup().await;
+
+        assert!(matches!(
+            bus.dispatch(&framework, "other", b"{\"aggregate_id\": 1}").await,
+            Err(FrameworkError::NoSuchQuery(name)) if name == "other"
+        ));
+    }
+
+    #[tokio::test]
+    async fn undecodable_payloads_are_refused() {
+        let (framework, bus) = setup().await;
+
+        assert!(matches!(
+            bus.dispatch(&framework, "total", b"{}").await,
+            Err(FrameworkError::SerializationError(_))
+        ));
+    }
+}
@@ -1,14 +1,21 @@
+use alloc::vec::Vec;
 use core::{any::TypeId, future::Future};
 
 use crate::{as_any::AsAny, event::Event, Result};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadModelUpdate {
+    Upsert,
+    Delete,
+}
+
 pub trait ReadModel: Sync + Send + Default
 where
     Self: Sized + 'static,
 {
     type Event: Event;
 
-    fn apply_event(&mut self, event: &Self::Event) -> Result<()>;
+    fn apply_event(&mut self, event: &Self::Event) -> Result<ReadModelUpdate>;
 }
 
 pub trait ReadModelStore: Sync + Send + AsAny {
@@ -29,22 +36,41 @@ pub trait ReadModelStore: Sync + Send + AsAny {
         self.as_any().downcast_ref()
     }
 
-    fn update_read_model<E>(&self, id: u64, events: &[E]) -> impl Future<Output = Result<()>> + Send
+    fn update_read_model<E>(
+        &self,
+        id: u64,
+        version: u32,
+        events: &[E],
+    ) -> impl Future<Output = Result<()>> + Send
     where
         E: Event + 'static,
     {
         async move {
+            // nothing happened, a missing or deleted read model must stay that way
+            if events.is_empty() {
+                return Ok(());
+            }
+
             let mut read_model = self.read(id).await?.unwrap_or_default();
+            let mut update = ReadModelUpdate::Upsert;
 
             for e in events {
                 let e = e
                     .as_any()
                     .downcast_ref::<<Self::ReadModel as ReadModel>::Event>()
                     .unwrap();
-                read_model.apply_event(e)?;
+
+                // events following a deletion start over from a fresh read model
+                if update == ReadModelUpdate::Delete {
+                    read_model = Default::default();
+                }
+                update = read_model.apply_event(e)?;
             }
 
-            self.save(id, &read_model).await?;
+            match update {
+                ReadModelUpdate::Upsert => self.save(id, version, &read_model).await?,
+                ReadModelUpdate::Delete => self.delete(id, version).await?,
+            }
 
             Ok(())
         }
@@ -54,8 +80,48 @@ pub trait ReadModelStore: Sync + Send + AsAny {
     fn save(
         &self,
         id: u64,
+        version: u32,
         read_model: &Self::ReadModel,
     ) -> impl Future<Output = Result<()>> + Send;
+    // deleted read models should keep reporting `version` as their position
+    fn delete(&self, id: u64, version: u32) -> impl Future<Output = Result<()>> + Send;
+    // version of the last event applied to the read model, 0 if none
+    fn position(&self, id: u64) -> impl Future<Output = Result<u32>> + Send;
+
+    // drops the read model's contents for good, used when its aggregate is shredded
+    fn purge(&self, id: u64) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let version = self.position(id).await?;
+            self.delete(id, version).await
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Page<M> {
+    pub items: Vec<(u64, M)>,
+    pub next_cursor: Option<u64>,
+}
+
+pub trait ReadModelScan: ReadModelStore {
+    // returns up to `limit` read models with id greater than `cursor`, in ascending id order,
+    // fails with `InvalidScanLimit` for a `limit` of 0, whose page couldn't tell if there's more
+    fn scan<P>(
+        &self,
+        cursor: Option<u64>,
+        limit: usize,
+        predicate: P,
+    ) -> impl Future<Output = Result<Page<Self::ReadModel>>> + Send
+    where
+        P: Fn(&Self::ReadModel) -> bool + Send;
+
+    fn list(
+        &self,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> impl Future<Output = Result<Page<Self::ReadModel>>> + Send {
+        self.scan(cursor, limit, |_| true)
+    }
 }
 
 pub trait ReadModelStores {
@@ -66,10 +132,16 @@ pub trait ReadModelStores {
     fn update_read_model<E>(
         &self,
         id: u64,
+        version: u32,
         events: &[E],
     ) -> impl Future<Output = Result<()>> + Send
     where
         E: Event + 'static;
+
+    // purges the read models projected from events of type `E`
+    fn purge_read_model<E>(&self, id: u64) -> impl Future<Output = Result<()>> + Send
+    where
+        E: Event + 'static;
 }
 
 // TODO macro..
@@ -81,7 +153,14 @@ impl ReadModelStores for () {
         None
     }
 
-    async fn update_read_model<E>(&self, _id: u64, _events: &[E]) -> Result<()>
+    async fn update_read_model<E>(&self, _id: u64, _version: u32, _events: &[E]) -> Result<()>
+    where
+        E: Event + 'static,
+    {
+        Ok(())
+    }
+
+    async fn purge_read_model<E>(&self, _id: u64) -> Result<()>
     where
         E: Event + 'static,
     {
@@ -104,12 +183,23 @@ where
         }
     }
 
-    async fn update_read_model<E>(&self, id: u64, events: &[E]) -> Result<()>
+    async fn update_read_model<E>(&self, id: u64, version: u32, events: &[E]) -> Result<()>
+    where
+        E: Event + 'static,
+    {
+        if TypeId::of::<E>() == S1::read_model_event_type() {
+            self.0.update_read_model(id, version, events).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn purge_read_model<E>(&self, id: u64) -> Result<()>
     where
         E: Event + 'static,
     {
         if TypeId::of::<E>() == S1::read_model_event_type() {
-            self.0.update_read_model(id, events).await?;
+            self.0.purge(id).await?;
         }
 
         Ok(())
@@ -134,15 +224,29 @@ where
         }
     }
 
-    async fn update_read_model<E>(&self, id: u64, events: &[E]) -> Result<()>
+    async fn update_read_model<E>(&self, id: u64, version: u32, events: &[E]) -> Result<()>
     where
         E: Event + 'static,
     {
         if TypeId::of::<E>() == S1::read_model_event_type() {
-            self.0.update_read_model(id, events).await?;
+            self.0.update_read_model(id, version, events).await?;
         }
         if TypeId::of::<E>() == S2::read_model_event_type() {
-            self.1.update_read_model(id, events).await?;
+            self.1.update_read_model(id, version, events).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn purge_read_model<E>(&self, id: u64) -> Result<()>
+    where
+        E: Event + 'static,
+    {
+        if TypeId::of::<E>() == S1::read_model_event_type() {
+            self.0.purge(id).await?;
+        }
+        if TypeId::of::<E>() == S2::read_model_event_type() {
+            self.1.purge(id).await?;
         }
 
         Ok(())
@@ -170,20 +274,66 @@ where
         }
     }
 
-    async fn update_read_model<E>(&self, id: u64, events: &[E]) -> Result<()>
+    async fn update_read_model<E>(&self, id: u64, version: u32, events: &[E]) -> Result<()>
     where
         E: Event + 'static,
     {
         if TypeId::of::<E>() == S1::read_model_event_type() {
-            self.0.update_read_model(id, events).await?;
+            self.0.update_read_model(id, version, events).await?;
         }
         if TypeId::of::<E>() == S2::read_model_event_type() {
-            self.1.update_read_model(id, events).await?;
+            self.1.update_read_model(id, version, events).await?;
         }
         if TypeId::of::<E>() == S3::read_model_event_type() {
-            self.2.update_read_model(id, events).await?;
+            self.2.update_read_model(id, version, events).await?;
         }
 
         Ok(())
     }
+
+    async fn purge_read_model<E>(&self, id: u64) -> Result<()>
+    where
+        E: Event + 'static,
+    {
+        if TypeId::of::<E>() == S1::read_model_event_type() {
+            self.0.purge(id).await?;
+        }
+        if TypeId::of::<E>() == S2::read_model_event_type() {
+            self.1.purge(id).await?;
+        }
+        if TypeId::of::<E>() == S3::read_model_event_type() {
+            self.2.purge(id).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::testing::{InMemoryReadModelStore, ReadModelTestFixture, TestEvent, TestReadModel};
+
+    #[tokio::test]
+    async fn empty_batch_keeps_deleted_read_model_deleted() {
+        ReadModelTestFixture::<TestReadModel>::given(vec![TestEvent::Added(1)])
+            .when(vec![TestEvent::Removed])
+            .when(vec![])
+            .then_expect(None)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn empty_batch_creates_no_read_model() {
+        let store = InMemoryReadModelStore::<TestReadModel>::new();
+        store
+            .update_read_model::<TestEvent>(7, 0, &[])
+            .await
+            .unwrap();
+
+        assert_eq!(store.read(7).await.unwrap(), None);
+        assert!(store.list(None, 10).await.unwrap().items.is_empty());
+    }
 }
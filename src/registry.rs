@@ -0,0 +1,272 @@
+use alloc::collections::BTreeMap;
+use core::any::TypeId;
+
+use crate::{
+    aggregate::{Aggregate, AggregateTypeId},
+    error::FrameworkError,
+    event::{Event, EventType, EventTypeId},
+    Result,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AggregateType {
+    pub id: AggregateTypeId,
+    pub name: &'static str,
+    pub events: &'static [EventType],
+}
+
+impl AggregateType {
+    pub fn event(&self, event_type_id: EventTypeId) -> Option<&EventType> {
+        self.events.iter().find(|e| e.id == event_type_id)
+    }
+}
+
+struct Registered {
+    aggregate: TypeId,
+    // `A::Event`, which read model stores are matched by
+    event: TypeId,
+    aggregate_type: AggregateType,
+}
+
+// aggregate and event types known to the framework, by id
+#[derive(Default)]
+pub struct EventRegistry {
+    aggregates: BTreeMap<AggregateTypeId, Registered>,
+}
+
+impl EventRegistry {
+    pub fn new() -> Self {
+        Self {
+            aggregates: BTreeMap::new(),
+        }
+    }
+
+    // fails if another aggregate uses the same type id, or the aggregate's events share an id
+    pub fn register<A>(&mut self) -> Result<()>
+    where
+        A: Aggregate + 'static,
+        A::Event: 'static,
+    {
+        let events = <A::Event as Event>::event_types();
+        for (i, event) in events.iter().enumerate() {
+            if events[..i].iter().any(|e| e.id == event.id) {
+                return Err(FrameworkError::DuplicateEventTypeId(A::type_id(), event.id));
+            }
+        }
+
+        match self.aggregates.get(&A::type_id()) {
+            Some(registered) if registered.aggregate != TypeId::of::<A>() => {
+                Err(FrameworkError::DuplicateAggregateTypeId(A::type_id()))
+            }
+            Some(_) => Ok(()),
+            None => {
+                let registered = Registered {
+                    aggregate: TypeId::of::<A>(),
+                    event: TypeId::of::<A::Event>(),
+                    aggregate_type: AggregateType {
+                        id: A::type_id(),
+                        name: A::type_name(),
+                        events,
+                    },
+                };
+                self.aggregates.insert(A::type_id(), registered);
+
+                Ok(())
+            }
+        }
+    }
+
+    // in aggregate type id order
+    pub fn aggregates(&self) -> impl Iterator<Item = &AggregateType> {
+        self.aggregates.values().map(|x| &x.aggregate_type)
+    }
+
+    pub fn aggregate(&self, aggregate_type_id: AggregateTypeId) -> Option<&AggregateType> {
+        self.aggregates
+            .get(&aggregate_type_id)
+            .map(|x| &x.aggregate_type)
+    }
+
+    pub(crate) fn event_type_of(&self, aggregate_type_id: AggregateTypeId) -> Option<TypeId> {
+        self.aggregates.get(&aggregate_type_id).map(|x| x.event)
+    }
+
+    pub fn event(
+        &self,
+        aggregate_type_id: AggregateTypeId,
+        event_type_id: EventTypeId,
+    ) -> Option<&EventType> {
+        self.aggregate(aggregate_type_id)?.event(event_type_id)
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+    use core::marker::PhantomData;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{
+        command::Command,
+        testing::{TestAggregate, TestError, TestEvent},
+    };
+
+    struct Nothing<A>(PhantomData<A>);
+
+    impl<A> Command for Nothing<A>
+    where
+        A: Aggregate<Command = Self> + 'static,
+    {
+        type Aggregate = A;
+
+        fn aggregate_id(&self) -> u64 {
+            1
+        }
+    }
+
+    // another aggregate with `TestAggregate`'s events, under type id `ID`
+    #[derive(Default, Serialize, Deserialize)]
+    struct Impostor<const ID: u32>;
+
+    impl<const ID: u32> Aggregate for Impostor<ID> {
+        type Command = Nothing<Self>;
+        type Event = TestEvent;
+        type Error = TestError;
+
+        fn type_id() -> AggregateTypeId {
+            ID
+        }
+
+        fn type_name() -> &'static str {
+            "Impostor"
+        }
+
+        fn handle(&self, _: Self::Command) -> core::result::Result<Vec<TestEvent>, TestError> {
+            Ok(vec![])
+        }
+
+        fn apply_events(&mut self, _: Vec<TestEvent>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum ClashingEvent {
+        Opened,
+        Closed,
+    }
+
+    impl Event for ClashingEvent {
+        fn type_id(&self) -> EventTypeId {
+            1
+        }
+
+        fn type_name(&self) -> &'static str {
+            match self {
+                ClashingEvent::Opened => "Opened",
+                ClashingEvent::Closed => "Closed",
+            }
+        }
+
+        fn event_types() -> &'static [EventType] {
+            &[
+                EventType {
+                    id: 1,
+                    name: "Opened",
+                    revision: 1,
+                },
+                EventType {
+                    id: 1,
+                    name: "Closed",
+                    revision: 1,
+                },
+            ]
+        }
+    }
+
+    #[derive(Default, Serialize, Deserialize)]
+    struct Clashing;
+
+    impl Aggregate for Clashing {
+        type Command = Nothing<Self>;
+        type Event = ClashingEvent;
+        type Error = TestError;
+
+        fn type_id() -> AggregateTypeId {
+            0xc1a5
+        }
+
+        fn type_name() -> &'static str {
+            "Clashing"
+        }
+
+        fn handle(&self, _: Self::Command) -> core::result::Result<Vec<ClashingEvent>, TestError> {
+            Ok(vec![])
+        }
+
+        fn apply_events(&mut self, _: Vec<ClashingEvent>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn aggregates_sharing_a_type_id_are_refused() {
+        let mut registry = EventRegistry::new();
+        registry.register::<TestAggregate>().unwrap();
+        // registering the same aggregate again is fine
+        registry.register::<TestAggregate>().unwrap();
+
+        assert!(matches!(
+            registry.register::<Impostor<0x7e57>>(),
+            Err(FrameworkError::DuplicateAggregateTypeId(0x7e57))
+        ));
+        assert_eq!(registry.aggregate(0x7e57).unwrap().name, "TestAggregate");
+    }
+
+    #[test]
+    fn events_sharing_a_type_id_are_refused() {
+        let mut registry = EventRegistry::new();
+
+        assert!(matches!(
+            registry.register::<Clashing>(),
+            Err(FrameworkError::DuplicateEventTypeId(0xc1a5, 1))
+        ));
+        assert!(registry.aggregate(0xc1a5).is_none());
+    }
+
+    #[test]
+    fn registered_types_are_enumerated() {
+        let mut registry = EventRegistry::new();
+        registry.register::<TestAggregate>().unwrap();
+        registry.register::<Impostor<1>>().unwrap();
+
+        let aggregates = registry
+            .aggregates()
+            .map(|x| (x.id, x.name))
+            .collect::<Vec<_>>();
+        assert_eq!(aggregates, vec![(1, "Impostor"), (0x7e57, "TestAggregate")]);
+
+        let aggregate = registry.aggregate(0x7e57).unwrap();
+        let events = aggregate
+            .events
+            .iter()
+            .map(|x| (x.id, x.name, x.revision))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![(1, "Added", 1), (2, "Renamed", 1), (3, "Removed", 1)]
+        );
+        assert!(registry.aggregate(2).is_none());
+
+        let event = TestEvent::Renamed(String::from("a"));
+        let registered = registry.event(0x7e57, event.type_id()).unwrap();
+        assert_eq!(
+            (registered.name, registered.revision),
+            (event.type_name(), event.revision())
+        );
+        assert!(registry.event(0x7e57, 4).is_none());
+        assert!(registry.event(2, 1).is_none());
+    }
+}
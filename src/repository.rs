@@ -1,6 +1,42 @@
+use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use crate::{aggregate::Aggregate, event::EventStore, snapshot::SnapshotStore, Result};
+use crate::{
+    aggregate::Aggregate,
+    error::FrameworkError,
+    event::{EventStore, VersionedEvent},
+    snapshot::SnapshotStore,
+    telemetry::{counter, histogram},
+    Result,
+};
+
+#[cfg(feature = "std")]
+use crate::cache::AggregateCache;
+
+// applies events following `version`, which must be contiguous
+pub(crate) fn apply_events<A>(
+    version: &mut u32,
+    aggregate: &mut A,
+    events: Vec<VersionedEvent<A::Event>>,
+) -> Result<()>
+where
+    A: Aggregate,
+{
+    let mut aggregate_events = Vec::with_capacity(events.len());
+    for event in events {
+        if event.version != *version + 1 {
+            return Err(FrameworkError::InvalidEventVersion(
+                *version + 1,
+                event.version,
+            ));
+        }
+
+        *version = event.version;
+        aggregate_events.push(event.event);
+    }
+
+    aggregate.apply_events(aggregate_events)
+}
 
 pub struct AggregateRepository<'a, A, E, S>
 where
@@ -10,6 +46,8 @@ where
 {
     event_store: &'a E,
     snapshot_store: &'a S,
+    #[cfg(feature = "std")]
+    cache: Option<&'a AggregateCache>,
     _phantom: PhantomData<A>,
 }
 
@@ -23,33 +61,147 @@ where
         Self {
             event_store,
             snapshot_store,
+            #[cfg(feature = "std")]
+            cache: None,
             _phantom: PhantomData,
         }
     }
 
-    pub async fn read(&self, aggregate_id: u64) -> Result<A> {
-        let mut aggregate = self
-            .snapshot_store
-            .read::<A>(aggregate_id)
-            .await?
-            .unwrap_or_default();
+    #[cfg(feature = "std")]
+    pub(crate) fn with_cache(mut self, cache: Option<&'a AggregateCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub async fn read(&self, aggregate_id: u64) -> Result<Option<(u32, A)>>
+    where
+        A: 'static,
+    {
+        #[cfg(feature = "std")]
+        if let Some(cache) = self.cache {
+            let cached = cache.take::<A>(aggregate_id);
+
+            counter!(
+                "framework_aggregate_cache_reads_total",
+                "aggregate" => A::type_name(),
+                "outcome" => if cached.is_some() { "hit" } else { "miss" },
+            );
+
+            return self.read_from(aggregate_id, cached).await;
+        }
+
+        self.read_from(aggregate_id, None).await
+    }
+
+    // catches `cached` up with the store, events appended elsewhere since it was kept are applied,
+    // or reads the aggregate when there's nothing cached
+    pub(crate) async fn read_from(
+        &self,
+        aggregate_id: u64,
+        cached: Option<(u32, A)>,
+    ) -> Result<Option<(u32, A)>> {
+        let Some((mut version, mut aggregate)) = cached else {
+            return self.load(aggregate_id).await;
+        };
+
+        let events = self.event_store.read::<A>(aggregate_id, version).await?;
+
+        histogram!(
+            "framework_events_replayed",
+            events.len(),
+            "aggregate" => A::type_name(),
+        );
 
-        let events = self
-            .event_store
-            .read::<A>(aggregate_id, aggregate.version())
+        apply_events(&mut version, &mut aggregate, events)?;
+
+        Ok(Some((version, aggregate)))
+    }
+
+    async fn load(&self, aggregate_id: u64) -> Result<Option<(u32, A)>> {
+        let snapshot = self.snapshot_store.read::<A>(aggregate_id).await?;
+        let has_snapshot = snapshot.is_some();
+        let (mut version, mut aggregate) = snapshot.unwrap_or_default();
+
+        counter!(
+            "framework_snapshot_reads_total",
+            "aggregate" => A::type_name(),
+            "outcome" => if has_snapshot { "hit" } else { "miss" },
+        );
+
+        let events = self.event_store.read::<A>(aggregate_id, version).await?;
+
+        histogram!(
+            "framework_events_replayed",
+            events.len(),
+            "aggregate" => A::type_name(),
+        );
+
+        if !has_snapshot && events.is_empty() {
+            return Ok(None);
+        }
+
+        apply_events(&mut version, &mut aggregate, events)?;
+
+        Ok(Some((version, aggregate)))
+    }
+
+    // applies the saved events to `aggregate`, which is at `expected_version`, and returns it
+    pub async fn save(
+        &self,
+        aggregate_id: u64,
+        expected_version: u32,
+        aggregate: A,
+        events: &[A::Event],
+    ) -> Result<Option<(u32, A)>> {
+        self.event_store
+            .save::<A>(aggregate_id, expected_version, events)
             .await?;
 
-        aggregate.apply_events(events)?;
+        // events are read back as stored rather than cloned
+        let aggregate = if expected_version == 0 && events.is_empty() {
+            None
+        } else {
+            let (mut version, mut aggregate) = (expected_version, aggregate);
+            let events = self.event_store.read::<A>(aggregate_id, version).await?;
+            apply_events(&mut version, &mut aggregate, events)?;
+
+            Some((version, aggregate))
+        };
+
+        // update snapshot
+        if let Some((version, aggregate)) = &aggregate {
+            self.snapshot_store
+                .save(aggregate_id, *version, aggregate)
+                .await?;
+        }
 
         Ok(aggregate)
     }
 
-    pub async fn save(&self, aggregate_id: u64, events: &[A::Event]) -> Result<()> {
-        self.event_store.save::<A>(aggregate_id, events).await?;
+    // hands the aggregate to the cache for the next command, if there is one
+    #[cfg(feature = "std")]
+    pub(crate) fn keep(&self, aggregate_id: u64, aggregate: Option<(u32, A)>)
+    where
+        A: 'static,
+    {
+        if let (Some(cache), Some((version, aggregate))) = (self.cache, aggregate) {
+            cache.put(aggregate_id, version, aggregate);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn evict(&self, aggregate_id: u64) {
+        if let Some(cache) = self.cache {
+            cache.remove::<A>(aggregate_id);
+        }
+    }
 
-        // update snapshot
-        let aggregate = self.read(aggregate_id).await?;
-        self.snapshot_store.save(aggregate_id, &aggregate).await?;
+    pub async fn delete(&self, aggregate_id: u64) -> Result<()> {
+        #[cfg(feature = "std")]
+        self.evict(aggregate_id);
+
+        self.event_store.tombstone::<A>(aggregate_id).await?;
+        self.snapshot_store.delete::<A>(aggregate_id).await?;
 
         Ok(())
     }
@@ -0,0 +1,92 @@
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::future::Future;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{aggregate::Aggregate, command::Command, event::Event, BoxFuture, Result};
+
+pub type SagaTypeId = u32;
+
+pub trait Saga: Sync + Send + Default + Serialize + DeserializeOwned + 'static {
+    type Aggregate: Aggregate + 'static;
+    type Command: Command + Send + 'static;
+
+    fn type_id() -> SagaTypeId
+    where
+        Self: Sized;
+    fn correlation_id(
+        aggregate_id: u64,
+        event: &<Self::Aggregate as Aggregate>::Event,
+    ) -> Option<u64>;
+    fn handle(
+        &mut self,
+        event: &<Self::Aggregate as Aggregate>::Event,
+    ) -> Result<Vec<Self::Command>>;
+
+    // deadline is compared against the `now` passed to `Framework::handle_saga_timeouts`
+    fn deadline(&self) -> Option<u64> {
+        None
+    }
+
+    fn handle_timeout(&mut self) -> Result<Vec<Self::Command>> {
+        Ok(Vec::new())
+    }
+}
+
+pub trait SagaStore: Sync + Send + 'static {
+    // the saga with its version, which is bumped by every save
+    fn read<S>(&self, correlation_id: u64) -> impl Future<Output = Result<Option<(u32, S)>>> + Send
+    where
+        S: Saga;
+    // fails with `ConcurrencyError` unless the stored version is `expected_version`, 0 if there's
+    // no saga stored yet
+    fn save<S>(
+        &self,
+        correlation_id: u64,
+        expected_version: u32,
+        saga: &S,
+    ) -> impl Future<Output = Result<()>> + Send
+    where
+        S: Saga;
+    fn expired<S>(&self, now: u64) -> impl Future<Output = Result<Vec<u64>>> + Send
+    where
+        S: Saga;
+}
+
+// lets the store be shared, e.g. to inspect sagas after handing it to `Framework::register_saga`
+impl<T> SagaStore for Arc<T>
+where
+    T: SagaStore,
+{
+    async fn read<S>(&self, correlation_id: u64) -> Result<Option<(u32, S)>>
+    where
+        S: Saga,
+    {
+        (**self).read(correlation_id).await
+    }
+
+    async fn save<S>(&self, correlation_id: u64, expected_version: u32, saga: &S) -> Result<()>
+    where
+        S: Saga,
+    {
+        (**self).save(correlation_id, expected_version, saga).await
+    }
+
+    async fn expired<S>(&self, now: u64) -> Result<Vec<u64>>
+    where
+        S: Saga,
+    {
+        (**self).expired::<S>(now).await
+    }
+}
+
+type BoxedSagaEventHandler<F> =
+    Box<dyn for<'a> Fn(&'a F, u64, &'a dyn Event) -> BoxFuture<'a, Result<()>> + Sync + Send>;
+type BoxedSagaTimeoutHandler<F> =
+    Box<dyn for<'a> Fn(&'a F, u64) -> BoxFuture<'a, Result<()>> + Sync + Send>;
+
+pub(crate) struct SagaHandler<F> {
+    pub name: &'static str,
+    pub on_event: BoxedSagaEventHandler<F>,
+    pub on_timeout: BoxedSagaTimeoutHandler<F>,
+}
@@ -0,0 +1,622 @@
+use alloc::{format, string::ToString, vec::Vec};
+use core::{marker::PhantomData, ops::Bound};
+use std::path::Path;
+
+use ::sled::{
+    transaction::{ConflictableTransactionError, TransactionError},
+    Batch, Config, Db, Transactional, Tree,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    aggregate::{Aggregate, AggregateTypeId},
+    cipher::{Cipher, NoCipher},
+    codec::Codec,
+    error::FrameworkError,
+    event::{EventStore, GlobalEvent, VersionedEvent},
+    read_model::{Page, ReadModel, ReadModelScan, ReadModelStore},
+    snapshot::SnapshotStore,
+    Result,
+};
+
+// keys are big endian so that trees iterate in stream, version and position order
+//
+// events:     aggregate type, aggregate id, version -> codec, position, data
+// log:        position -> events key
+// streams:    aggregate type, aggregate id -> version, and `sequence` -> last position
+// tombstones: aggregate type, aggregate id -> ()
+// snapshots:  aggregate type, aggregate id -> version, codec, data
+// read models, one tree per store: id -> version, and codec, data unless deleted
+
+const SEQUENCE_KEY: &[u8] = b"sequence";
+
+fn database_error(e: ::sled::Error) -> FrameworkError {
+    FrameworkError::DatabaseError(e.to_string())
+}
+
+fn transaction_error(e: TransactionError<FrameworkError>) -> FrameworkError {
+    match e {
+        TransactionError::Abort(e) => e,
+        TransactionError::Storage(e) => database_error(e),
+    }
+}
+
+fn corrupt_value() -> FrameworkError {
+    FrameworkError::DatabaseError("malformed value".to_string())
+}
+
+fn stream_key(aggregate_type: AggregateTypeId, aggregate_id: u64) -> [u8; 12] {
+    let mut key = [0; 12];
+    key[..4].copy_from_slice(&aggregate_type.to_be_bytes());
+    key[4..].copy_from_slice(&aggregate_id.to_be_bytes());
+    key
+}
+
+fn event_key(aggregate_type: AggregateTypeId, aggregate_id: u64, version: u32) -> [u8; 16] {
+    let mut key = [0; 16];
+    key[..12].copy_from_slice(&stream_key(aggregate_type, aggregate_id));
+    key[12..].copy_from_slice(&version.to_be_bytes());
+    key
+}
+
+fn read_u32(bytes: &[u8]) -> Result<u32> {
+    bytes
+        .get(..4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(corrupt_value)
+}
+
+fn read_u64(bytes: &[u8]) -> Result<u64> {
+    bytes
+        .get(..8)
+        .map(|b| u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+        .ok_or_else(corrupt_value)
+}
+
+#[derive(Clone)]
+pub struct SledDatabase {
+    db: Db,
+    events: Tree,
+    log: Tree,
+    streams: Tree,
+    tombstones: Tree,
+    snapshots: Tree,
+}
+
+impl SledDatabase {
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(::sled::open(path).map_err(database_error)?)
+    }
+
+    // removed from disk once the last handle is dropped
+    pub fn temporary() -> Result<Self> {
+        Self::new(
+            Config::new()
+                .temporary(true)
+                .open()
+                .map_err(database_error)?,
+        )
+    }
+
+    fn new(db: Db) -> Result<Self> {
+        Ok(Self {
+            events: db.open_tree("events").map_err(database_error)?,
+            log: db.open_tree("log").map_err(database_error)?,
+            streams: db.open_tree("streams").map_err(database_error)?,
+            tombstones: db.open_tree("tombstones").map_err(database_error)?,
+            snapshots: db.open_tree("snapshots").map_err(database_error)?,
+            db,
+        })
+    }
+
+    pub fn event_store<C>(&self) -> SledEventStore<C>
+    where
+        C: Codec,
+    {
+        SledEventStore {
+            database: self.clone(),
+            cipher: NoCipher,
+            _codec: PhantomData,
+        }
+    }
+
+    pub fn snapshot_store<C>(&self) -> SledSnapshotStore<C>
+    where
+        C: Codec,
+    {
+        SledSnapshotStore {
+            database: self.clone(),
+            _codec: PhantomData,
+        }
+    }
+
+    pub fn read_model_store<M, C>(&self, name: &str) -> Result<SledReadModelStore<M, C>>
+    where
+        M: ReadModel + Serialize + DeserializeOwned,
+        C: Codec,
+    {
+        Ok(SledReadModelStore {
+            tree: self
+                .db
+                .open_tree(format!("read_model.{}", name))
+                .map_err(database_error)?,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+pub struct SledEventStore<C, K = NoCipher>
+where
+    C: Codec,
+{
+    database: SledDatabase,
+    cipher: K,
+    _codec: PhantomData<C>,
+}
+
+impl<C> SledEventStore<C>
+where
+    C: Codec,
+{
+    // event payloads are encrypted with `cipher` before they're stored
+    pub fn with_cipher<K>(self, cipher: K) -> SledEventStore<C, K>
+    where
+        K: Cipher,
+    {
+        SledEventStore {
+            database: self.database,
+            cipher,
+            _codec: PhantomData,
+        }
+    }
+}
+
+impl<C, K> SledEventStore<C, K>
+where
+    C: Codec,
+    K: Cipher,
+{
+    // returns up to `limit` events of `A` with a global position greater than `after`,
+    // positions are assigned in commit order so the log can be replayed to rebuild projections
+    pub async fn read_all<A>(&self, after: u64, limit: usize) -> Result<Vec<GlobalEvent<A::Event>>>
+    where
+        A: Aggregate,
+    {
+        let mut events = Vec::new();
+
+        let range = (Bound::Excluded(after.to_be_bytes()), Bound::Unbounded);
+        for entry in self.database.log.range(range) {
+            if events.len() == limit {
+                break;
+            }
+
+            let (position, key) = entry.map_err(database_error)?;
+            if read_u32(&key)? != A::type_id() {
+                continue;
+            }
+
+            // tombstoned streams leave no events behind
+            let Some(value) = self.database.events.get(&key).map_err(database_error)? else {
+                continue;
+            };
+            if value.len() < 9 {
+                return Err(corrupt_value());
+            }
+
+            let aggregate_id = read_u64(&key[4..])?;
+            let data = match self
+                .cipher
+                .decrypt::<A>(aggregate_id, value[9..].to_vec())
+                .await
+            {
+                Ok(data) => data,
+                // shredded aggregates are skipped like tombstoned ones
+                Err(FrameworkError::AggregateShredded(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            events.push(GlobalEvent {
+                position: read_u64(&position)?,
+                aggregate_id,
+                version: read_u32(&key[12..])?,
+                event: C::decode_tagged(value[0], &data)?,
+            });
+        }
+
+        Ok(events)
+    }
+}
+
+impl<C, K> EventStore for SledEventStore<C, K>
+where
+    C: Codec + Sync + Send,
+    K: Cipher + 'static,
+{
+    async fn read<A>(
+        &self,
+        aggregate_id: u64,
+        from_version: u32,
+    ) -> Result<Vec<VersionedEvent<A::Event>>>
+    where
+        A: Aggregate,
+    {
+        if self
+            .database
+            .tombstones
+            .contains_key(stream_key(A::type_id(), aggregate_id))
+            .map_err(database_error)?
+        {
+            return Err(FrameworkError::AggregateDeleted(aggregate_id));
+        }
+
+        let Some(from_version) = from_version.checked_add(1) else {
+            return Ok(Vec::new());
+        };
+
+        let records = self
+            .database
+            .events
+            .range(
+                event_key(A::type_id(), aggregate_id, from_version)
+                    ..=event_key(A::type_id(), aggregate_id, u32::MAX),
+            )
+            .map(|entry| {
+                let (key, value) = entry.map_err(database_error)?;
+                if value.len() < 9 {
+                    return Err(corrupt_value());
+                }
+
+                Ok((read_u32(&key[12..])?, value[0], value[9..].to_vec()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut events = Vec::with_capacity(records.len());
+        for (version, codec_id, data) in records {
+            let data = self.cipher.decrypt::<A>(aggregate_id, data).await?;
+            events.push(VersionedEvent {
+                version,
+                event: C::decode_tagged(codec_id, &data)?,
+            });
+        }
+
+        Ok(events)
+    }
+
+    async fn save<A>(
+        &self,
+        aggregate_id: u64,
+        expected_version: u32,
+        events: &[A::Event],
+    ) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let mut encrypted = Vec::with_capacity(events.len());
+        for event in events {
+            let data = C::encode(event)?;
+            encrypted.push(self.cipher.encrypt::<A>(aggregate_id, data).await?);
+        }
+        let events = encrypted;
+        let stream = stream_key(A::type_id(), aggregate_id);
+
+        let database = &self.database;
+        (
+            &database.events,
+            &database.log,
+            &database.streams,
+            &database.tombstones,
+        )
+            .transaction(|(event_tree, log, streams, tombstones)| {
+                if tombstones.get(stream)?.is_some() {
+                    return Err(ConflictableTransactionError::Abort(
+                        FrameworkError::AggregateDeleted(aggregate_id),
+                    ));
+                }
+
+                let version = match streams.get(stream)? {
+                    Some(version) => {
+                        read_u32(&version).map_err(ConflictableTransactionError::Abort)?
+                    }
+                    None => 0,
+                };
+                if version != expected_version {
+                    return Err(ConflictableTransactionError::Abort(
+                        FrameworkError::ConcurrencyError,
+                    ));
+                }
+
+                // every append touches the sequence, so concurrent appends conflict and
+                // positions end up in commit order
+                let mut position = match streams.get(SEQUENCE_KEY)? {
+                    Some(position) => {
+                        read_u64(&position).map_err(ConflictableTransactionError::Abort)?
+                    }
+                    None => 0,
+                };
+
+                for (version, data) in (expected_version + 1..).zip(&events) {
+                    position += 1;
+
+                    let key = event_key(A::type_id(), aggregate_id, version);
+                    let mut value = Vec::with_capacity(9 + data.len());
+                    value.push(C::ID);
+                    value.extend_from_slice(&position.to_be_bytes());
+                    value.extend_from_slice(data);
+
+                    event_tree.insert(&key, value)?;
+                    log.insert(&position.to_be_bytes(), &key)?;
+                }
+
+                streams.insert(
+                    &stream,
+                    &(expected_version + events.len() as u32).to_be_bytes(),
+                )?;
+                streams.insert(SEQUENCE_KEY, &position.to_be_bytes())?;
+
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+
+        database.db.flush_async().await.map_err(database_error)?;
+
+        Ok(())
+    }
+
+    async fn tombstone<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let stream = stream_key(A::type_id(), aggregate_id);
+
+        // transactional so that it conflicts with appends to the stream
+        self.database
+            .tombstones
+            .transaction(|tombstones| {
+                tombstones.insert(&stream, &[])?;
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+
+        // the tombstone already hides the stream, its events are removed afterwards
+        let mut events = Batch::default();
+        let mut log = Batch::default();
+        for entry in self.database.events.scan_prefix(stream) {
+            let (key, value) = entry.map_err(database_error)?;
+            log.remove(value.get(1..9).ok_or_else(corrupt_value)?);
+            events.remove(key);
+        }
+        self.database.log.apply_batch(log).map_err(database_error)?;
+        self.database
+            .events
+            .apply_batch(events)
+            .map_err(database_error)?;
+
+        self.database
+            .db
+            .flush_async()
+            .await
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.cipher.shred::<A>(aggregate_id).await
+    }
+}
+
+pub struct SledSnapshotStore<C>
+where
+    C: Codec,
+{
+    database: SledDatabase,
+    _codec: PhantomData<C>,
+}
+
+impl<C> SnapshotStore for SledSnapshotStore<C>
+where
+    C: Codec + Sync + Send,
+{
+    async fn read<A>(&self, aggregate_id: u64) -> Result<Option<(u32, A)>>
+    where
+        A: Aggregate,
+    {
+        let Some(value) = self
+            .database
+            .snapshots
+            .get(stream_key(A::type_id(), aggregate_id))
+            .map_err(database_error)?
+        else {
+            return Ok(None);
+        };
+        if value.len() < 5 {
+            return Err(corrupt_value());
+        }
+
+        Ok(Some((
+            read_u32(&value)?,
+            C::decode_tagged(value[4], &value[5..])?,
+        )))
+    }
+
+    async fn save<A>(&self, aggregate_id: u64, version: u32, aggregate: &A) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let data = C::encode(aggregate)?;
+
+        let mut value = Vec::with_capacity(5 + data.len());
+        value.extend_from_slice(&version.to_be_bytes());
+        value.push(C::ID);
+        value.extend_from_slice(&data);
+
+        self.database
+            .snapshots
+            .insert(stream_key(A::type_id(), aggregate_id), value)
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    async fn delete<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.database
+            .snapshots
+            .remove(stream_key(A::type_id(), aggregate_id))
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct SledReadModelStore<M, C>
+where
+    M: ReadModel + Serialize + DeserializeOwned,
+    C: Codec,
+{
+    tree: Tree,
+    _phantom: PhantomData<(M, C)>,
+}
+
+impl<M, C> SledReadModelStore<M, C>
+where
+    M: ReadModel + Serialize + DeserializeOwned,
+    C: Codec,
+{
+    // `None` for read models that were deleted
+    fn decode(value: &[u8]) -> Result<Option<M>> {
+        match value.get(4) {
+            Some(&codec_id) => Ok(Some(C::decode_tagged(codec_id, &value[5..])?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<M, C> ReadModelStore for SledReadModelStore<M, C>
+where
+    M: ReadModel + Serialize + DeserializeOwned,
+    C: Codec + Sync + Send + 'static,
+{
+    type ReadModel = M;
+
+    async fn read(&self, id: u64) -> Result<Option<M>> {
+        match self.tree.get(id.to_be_bytes()).map_err(database_error)? {
+            Some(value) => Self::decode(&value),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&self, id: u64, version: u32, read_model: &M) -> Result<()> {
+        let data = C::encode(read_model)?;
+
+        let mut value = Vec::with_capacity(5 + data.len());
+        value.extend_from_slice(&version.to_be_bytes());
+        value.push(C::ID);
+        value.extend_from_slice(&data);
+
+        self.tree
+            .insert(id.to_be_bytes(), value)
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: u64, version: u32) -> Result<()> {
+        self.tree
+            .insert(id.to_be_bytes(), &version.to_be_bytes())
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    async fn position(&self, id: u64) -> Result<u32> {
+        match self.tree.get(id.to_be_bytes()).map_err(database_error)? {
+            Some(value) => read_u32(&value),
+            None => Ok(0),
+        }
+    }
+}
+
+impl<M, C> ReadModelScan for SledReadModelStore<M, C>
+where
+    M: ReadModel + Serialize + DeserializeOwned,
+    C: Codec + Sync + Send + 'static,
+{
+    async fn scan<P>(&self, cursor: Option<u64>, limit: usize, predicate: P) -> Result<Page<M>>
+    where
+        P: Fn(&M) -> bool + Send,
+    {
+        if limit == 0 {
+            return Err(FrameworkError::InvalidScanLimit);
+        }
+
+        let range = match cursor {
+            Some(cursor) => (Bound::Excluded(cursor.to_be_bytes()), Bound::Unbounded),
+            None => (Bound::Unbounded, Bound::Unbounded),
+        };
+
+        let mut items = Vec::new();
+        for entry in self.tree.range(range) {
+            let (id, value) = entry.map_err(database_error)?;
+
+            let Some(read_model) = Self::decode(&value)? else {
+                continue;
+            };
+
+            if predicate(&read_model) {
+                items.push((read_u64(&id)?, read_model));
+                if items.len() > limit {
+                    break;
+                }
+            }
+        }
+
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|(id, _)| *id)
+        } else {
+            None
+        };
+
+        Ok(Page { items, next_cursor })
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::{codec::JsonCodec, testing};
+
+    #[tokio::test]
+    async fn event_store_conformance() {
+        let database = SledDatabase::temporary().unwrap();
+        testing::event_store_conformance(&database.event_store::<JsonCodec>()).await;
+    }
+
+    #[tokio::test]
+    async fn snapshot_store_conformance() {
+        let database = SledDatabase::temporary().unwrap();
+        testing::snapshot_store_conformance(&database.snapshot_store::<JsonCodec>()).await;
+    }
+
+    #[tokio::test]
+    async fn read_model_store_conformance() {
+        let database = SledDatabase::temporary().unwrap();
+        testing::read_model_store_conformance(
+            &database.read_model_store::<_, JsonCodec>("a").unwrap(),
+        )
+        .await;
+        testing::read_model_scan_conformance(
+            &database.read_model_store::<_, JsonCodec>("b").unwrap(),
+        )
+        .await;
+    }
+}
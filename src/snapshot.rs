@@ -3,10 +3,18 @@ use core::future::Future;
 use crate::{aggregate::Aggregate, Result};
 
 pub trait SnapshotStore {
-    fn read<A>(&self, aggregate_id: u64) -> impl Future<Output = Result<Option<A>>> + Send
+    fn read<A>(&self, aggregate_id: u64) -> impl Future<Output = Result<Option<(u32, A)>>> + Send
     where
         A: Aggregate;
-    fn save<A>(&self, aggregate_id: u64, aggregate: &A) -> impl Future<Output = Result<()>> + Send
+    fn save<A>(
+        &self,
+        aggregate_id: u64,
+        version: u32,
+        aggregate: &A,
+    ) -> impl Future<Output = Result<()>> + Send
+    where
+        A: Aggregate;
+    fn delete<A>(&self, aggregate_id: u64) -> impl Future<Output = Result<()>> + Send
     where
         A: Aggregate;
 }
@@ -14,14 +22,21 @@ pub trait SnapshotStore {
 pub struct DummySnapshotStore;
 
 impl SnapshotStore for DummySnapshotStore {
-    async fn read<A>(&self, _aggregate_id: u64) -> Result<Option<A>>
+    async fn read<A>(&self, _aggregate_id: u64) -> Result<Option<(u32, A)>>
     where
         A: Aggregate,
     {
         Ok(None)
     }
 
-    async fn save<A>(&self, _aggregate_id: u64, _aggregate: &A) -> Result<()>
+    async fn save<A>(&self, _aggregate_id: u64, _version: u32, _aggregate: &A) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        Ok(())
+    }
+
+    async fn delete<A>(&self, _aggregate_id: u64) -> Result<()>
     where
         A: Aggregate,
     {
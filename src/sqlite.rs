@@ -0,0 +1,557 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::marker::PhantomData;
+use std::{path::Path, sync::Mutex};
+
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    aggregate::Aggregate,
+    cipher::{Cipher, NoCipher},
+    codec::{Codec, CodecId},
+    error::FrameworkError,
+    event::{EventStore, VersionedEvent},
+    read_model::{Page, ReadModel, ReadModelScan, ReadModelStore},
+    snapshot::SnapshotStore,
+    Result,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        aggregate_type INTEGER NOT NULL,
+        aggregate_id INTEGER NOT NULL,
+        version INTEGER NOT NULL,
+        codec INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (aggregate_type, aggregate_id, version)
+    );
+    CREATE TABLE IF NOT EXISTS tombstones (
+        aggregate_type INTEGER NOT NULL,
+        aggregate_id INTEGER NOT NULL,
+        PRIMARY KEY (aggregate_type, aggregate_id)
+    );
+    CREATE TABLE IF NOT EXISTS snapshots (
+        aggregate_type INTEGER NOT NULL,
+        aggregate_id INTEGER NOT NULL,
+        version INTEGER NOT NULL,
+        codec INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (aggregate_type, aggregate_id)
+    );
+    CREATE TABLE IF NOT EXISTS read_models (
+        name TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        version INTEGER NOT NULL,
+        codec INTEGER,
+        data BLOB,
+        PRIMARY KEY (name, id)
+    );
+";
+
+fn database_error(e: rusqlite::Error) -> FrameworkError {
+    match e.sqlite_error_code() {
+        Some(ErrorCode::ConstraintViolation) => FrameworkError::ConcurrencyError,
+        _ => FrameworkError::DatabaseError(e.to_string()),
+    }
+}
+
+// SQLite integers are signed, ids are stored with the sign bit flipped so they still sort as
+// unsigned and scans page through ids of 2^63 and above in order
+fn to_sql(id: u64) -> i64 {
+    (id ^ (1 << 63)) as i64
+}
+
+fn from_sql(id: i64) -> u64 {
+    (id as u64) ^ (1 << 63)
+}
+
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDatabase {
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(Connection::open(path).map_err(database_error)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::new(Connection::open_in_memory().map_err(database_error)?)
+    }
+
+    fn new(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA).map_err(database_error)?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    pub fn event_store<C>(&self) -> SqliteEventStore<C>
+    where
+        C: Codec,
+    {
+        SqliteEventStore {
+            database: self.clone(),
+            cipher: NoCipher,
+            _codec: PhantomData,
+        }
+    }
+
+    pub fn snapshot_store<C>(&self) -> SqliteSnapshotStore<C>
+    where
+        C: Codec,
+    {
+        SqliteSnapshotStore {
+            database: self.clone(),
+            _codec: PhantomData,
+        }
+    }
+
+    pub fn read_model_store<M, C>(&self, name: &str) -> SqliteReadModelStore<M, C>
+    where
+        M: ReadModel + Serialize + DeserializeOwned,
+        C: Codec,
+    {
+        SqliteReadModelStore {
+            database: self.clone(),
+            name: name.to_string(),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn with_connection<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T>,
+    {
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|e| FrameworkError::DatabaseError(e.to_string()))?;
+
+        f(&mut connection).map_err(database_error)
+    }
+}
+
+pub struct SqliteEventStore<C, K = NoCipher>
+where
+    C: Codec,
+{
+    database: SqliteDatabase,
+    cipher: K,
+    _codec: PhantomData<C>,
+}
+
+impl<C> SqliteEventStore<C>
+where
+    C: Codec,
+{
+    // event payloads are encrypted with `cipher` before they're stored
+    pub fn with_cipher<K>(self, cipher: K) -> SqliteEventStore<C, K>
+    where
+        K: Cipher,
+    {
+        SqliteEventStore {
+            database: self.database,
+            cipher,
+            _codec: PhantomData,
+        }
+    }
+}
+
+impl<C, K> SqliteEventStore<C, K>
+where
+    C: Codec,
+{
+    fn is_tombstoned(
+        connection: &Connection,
+        aggregate_type: u32,
+        aggregate_id: u64,
+    ) -> rusqlite::Result<bool> {
+        connection
+            .query_row(
+                "SELECT 1 FROM tombstones WHERE aggregate_type = ?1 AND aggregate_id = ?2",
+                params![aggregate_type, to_sql(aggregate_id)],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|x| x.is_some())
+    }
+}
+
+impl<C, K> EventStore for SqliteEventStore<C, K>
+where
+    C: Codec + Sync + Send,
+    K: Cipher + 'static,
+{
+    async fn read<A>(
+        &self,
+        aggregate_id: u64,
+        from_version: u32,
+    ) -> Result<Vec<VersionedEvent<A::Event>>>
+    where
+        A: Aggregate,
+    {
+        let rows = self.database.with_connection(|connection| {
+            if Self::is_tombstoned(connection, A::type_id(), aggregate_id)? {
+                return Ok(None);
+            }
+
+            let mut statement = connection.prepare_cached(
+                "SELECT version, codec, data FROM events
+                 WHERE aggregate_type = ?1 AND aggregate_id = ?2 AND version > ?3
+                 ORDER BY version",
+            )?;
+
+            let rows = statement
+                .query_map(
+                    params![A::type_id(), to_sql(aggregate_id), from_version],
+                    |row| {
+                        Ok((
+                            row.get::<_, u32>(0)?,
+                            row.get::<_, CodecId>(1)?,
+                            row.get::<_, Vec<u8>>(2)?,
+                        ))
+                    },
+                )?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok(Some(rows))
+        })?;
+
+        let Some(rows) = rows else {
+            return Err(FrameworkError::AggregateDeleted(aggregate_id));
+        };
+
+        let mut events = Vec::with_capacity(rows.len());
+        for (version, codec_id, data) in rows {
+            let data = self.cipher.decrypt::<A>(aggregate_id, data).await?;
+            events.push(VersionedEvent {
+                version,
+                event: C::decode_tagged(codec_id, &data)?,
+            });
+        }
+
+        Ok(events)
+    }
+
+    async fn save<A>(
+        &self,
+        aggregate_id: u64,
+        expected_version: u32,
+        events: &[A::Event],
+    ) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let mut encrypted = Vec::with_capacity(events.len());
+        for event in events {
+            let data = C::encode(event)?;
+            encrypted.push(self.cipher.encrypt::<A>(aggregate_id, data).await?);
+        }
+        let events = encrypted;
+
+        self.database.with_connection(|connection| {
+            let transaction = connection.transaction()?;
+
+            if Self::is_tombstoned(&transaction, A::type_id(), aggregate_id)? {
+                return Ok(Err(FrameworkError::AggregateDeleted(aggregate_id)));
+            }
+
+            let version: u32 = transaction.query_row(
+                "SELECT COALESCE(MAX(version), 0) FROM events
+                 WHERE aggregate_type = ?1 AND aggregate_id = ?2",
+                params![A::type_id(), to_sql(aggregate_id)],
+                |row| row.get(0),
+            )?;
+            if version != expected_version {
+                return Ok(Err(FrameworkError::ConcurrencyError));
+            }
+
+            {
+                let mut statement = transaction.prepare_cached(
+                    "INSERT INTO events (aggregate_type, aggregate_id, version, codec, data)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?;
+                for (version, data) in (expected_version + 1..).zip(&events) {
+                    statement.execute(params![
+                        A::type_id(),
+                        to_sql(aggregate_id),
+                        version,
+                        C::ID,
+                        data
+                    ])?;
+                }
+            }
+
+            transaction.commit()?;
+
+            Ok(Ok(()))
+        })?
+    }
+
+    async fn tombstone<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.database.with_connection(|connection| {
+            let transaction = connection.transaction()?;
+
+            transaction.execute(
+                "INSERT OR IGNORE INTO tombstones (aggregate_type, aggregate_id) VALUES (?1, ?2)",
+                params![A::type_id(), to_sql(aggregate_id)],
+            )?;
+            transaction.execute(
+                "DELETE FROM events WHERE aggregate_type = ?1 AND aggregate_id = ?2",
+                params![A::type_id(), to_sql(aggregate_id)],
+            )?;
+
+            transaction.commit()
+        })
+    }
+
+    async fn shred<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.cipher.shred::<A>(aggregate_id).await
+    }
+}
+
+pub struct SqliteSnapshotStore<C>
+where
+    C: Codec,
+{
+    database: SqliteDatabase,
+    _codec: PhantomData<C>,
+}
+
+impl<C> SnapshotStore for SqliteSnapshotStore<C>
+where
+    C: Codec + Sync + Send,
+{
+    async fn read<A>(&self, aggregate_id: u64) -> Result<Option<(u32, A)>>
+    where
+        A: Aggregate,
+    {
+        let row = self.database.with_connection(|connection| {
+            connection
+                .query_row(
+                    "SELECT version, codec, data FROM snapshots
+                     WHERE aggregate_type = ?1 AND aggregate_id = ?2",
+                    params![A::type_id(), to_sql(aggregate_id)],
+                    |row| {
+                        Ok((
+                            row.get::<_, u32>(0)?,
+                            row.get::<_, CodecId>(1)?,
+                            row.get::<_, Vec<u8>>(2)?,
+                        ))
+                    },
+                )
+                .optional()
+        })?;
+
+        row.map(|(version, codec_id, data)| Ok((version, C::decode_tagged(codec_id, &data)?)))
+            .transpose()
+    }
+
+    async fn save<A>(&self, aggregate_id: u64, version: u32, aggregate: &A) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        let data = C::encode(aggregate)?;
+
+        self.database.with_connection(|connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO snapshots
+                 (aggregate_type, aggregate_id, version, codec, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![A::type_id(), to_sql(aggregate_id), version, C::ID, data],
+            )?;
+
+            Ok(())
+        })
+    }
+
+    async fn delete<A>(&self, aggregate_id: u64) -> Result<()>
+    where
+        A: Aggregate,
+    {
+        self.database.with_connection(|connection| {
+            connection.execute(
+                "DELETE FROM snapshots WHERE aggregate_type = ?1 AND aggregate_id = ?2",
+                params![A::type_id(), to_sql(aggregate_id)],
+            )?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct SqliteReadModelStore<M, C>
+where
+    M: ReadModel + Serialize + DeserializeOwned,
+    C: Codec,
+{
+    database: SqliteDatabase,
+    name: String,
+    _phantom: PhantomData<(M, C)>,
+}
+
+impl<M, C> ReadModelStore for SqliteReadModelStore<M, C>
+where
+    M: ReadModel + Serialize + DeserializeOwned,
+    C: Codec + Sync + Send + 'static,
+{
+    type ReadModel = M;
+
+    async fn read(&self, id: u64) -> Result<Option<M>> {
+        let row = self.database.with_connection(|connection| {
+            connection
+                .query_row(
+                    "SELECT codec, data FROM read_models WHERE name = ?1 AND id = ?2",
+                    params![self.name, to_sql(id)],
+                    |row| {
+                        Ok((
+                            row.get::<_, Option<CodecId>>(0)?,
+                            row.get::<_, Option<Vec<u8>>>(1)?,
+                        ))
+                    },
+                )
+                .optional()
+        })?;
+
+        match row {
+            Some((Some(codec_id), Some(data))) => Ok(Some(C::decode_tagged(codec_id, &data)?)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn save(&self, id: u64, version: u32, read_model: &M) -> Result<()> {
+        let data = C::encode(read_model)?;
+
+        self.database.with_connection(|connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO read_models (name, id, version, codec, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![self.name, to_sql(id), version, C::ID, data],
+            )?;
+
+            Ok(())
+        })
+    }
+
+    async fn delete(&self, id: u64, version: u32) -> Result<()> {
+        self.database.with_connection(|connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO read_models (name, id, version, codec, data)
+                 VALUES (?1, ?2, ?3, NULL, NULL)",
+                params![self.name, to_sql(id), version],
+            )?;
+
+            Ok(())
+        })
+    }
+
+    async fn position(&self, id: u64) -> Result<u32> {
+        let version = self.database.with_connection(|connection| {
+            connection
+                .query_row(
+                    "SELECT version FROM read_models WHERE name = ?1 AND id = ?2",
+                    params![self.name, to_sql(id)],
+                    |row| row.get::<_, u32>(0),
+                )
+                .optional()
+        })?;
+
+        Ok(version.unwrap_or(0))
+    }
+}
+
+impl<M, C> ReadModelScan for SqliteReadModelStore<M, C>
+where
+    M: ReadModel + Serialize + DeserializeOwned,
+    C: Codec + Sync + Send + 'static,
+{
+    async fn scan<P>(&self, cursor: Option<u64>, limit: usize, predicate: P) -> Result<Page<M>>
+    where
+        P: Fn(&M) -> bool + Send,
+    {
+        if limit == 0 {
+            return Err(FrameworkError::InvalidScanLimit);
+        }
+
+        let mut items = self.database.with_connection(|connection| {
+            let mut statement = connection.prepare_cached(
+                "SELECT id, codec, data FROM read_models
+                 WHERE name = ?1 AND (?2 IS NULL OR id > ?2) AND data IS NOT NULL
+                 ORDER BY id",
+            )?;
+
+            let mut rows = statement.query(params![self.name, cursor.map(to_sql)])?;
+
+            let mut items = Vec::new();
+            while let Some(row) = rows.next()? {
+                let id = from_sql(row.get(0)?);
+                let codec_id = row.get::<_, CodecId>(1)?;
+                let data = row.get::<_, Vec<u8>>(2)?;
+
+                let read_model = match C::decode_tagged::<M>(codec_id, &data) {
+                    Ok(x) => x,
+                    Err(e) => return Ok(Err(e)),
+                };
+
+                if predicate(&read_model) {
+                    items.push((id, read_model));
+                    if items.len() > limit {
+                        break;
+                    }
+                }
+            }
+
+            Ok(Ok(items))
+        })??;
+
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|(id, _)| *id)
+        } else {
+            None
+        };
+
+        Ok(Page { items, next_cursor })
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::{codec::JsonCodec, testing};
+
+    #[tokio::test]
+    async fn event_store_conformance() {
+        let database = SqliteDatabase::open_in_memory().unwrap();
+        testing::event_store_conformance(&database.event_store::<JsonCodec>()).await;
+    }
+
+    #[tokio::test]
+    async fn snapshot_store_conformance() {
+        let database = SqliteDatabase::open_in_memory().unwrap();
+        testing::snapshot_store_conformance(&database.snapshot_store::<JsonCodec>()).await;
+    }
+
+    #[tokio::test]
+    async fn read_model_store_conformance() {
+        let database = SqliteDatabase::open_in_memory().unwrap();
+        testing::read_model_store_conformance(&database.read_model_store::<_, JsonCodec>("a"))
+            .await;
+        testing::read_model_scan_conformance(&database.read_model_store::<_, JsonCodec>("b")).await;
+    }
+}
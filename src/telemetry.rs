@@ -0,0 +1,152 @@
+// Spans and metrics for the command, query and projection paths. Everything here compiles to
+// nothing unless the `tracing` or `metrics` feature is enabled, so call sites stay unconditional.
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Instrument;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn in_scope<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        f()
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) trait Instrument: Sized {
+    fn instrument(self, _span: Span) -> Self {
+        self
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<T> Instrument for T {}
+
+macro_rules! span {
+    ($name:literal $(, $field:ident = $value:expr)* $(,)?) => {{
+        #[cfg(feature = "tracing")]
+        let span = ::tracing::info_span!($name $(, $field = $value)*);
+        #[cfg(not(feature = "tracing"))]
+        let span = {
+            $(let _ = &$value;)*
+            $crate::telemetry::Span
+        };
+        span
+    }};
+}
+
+// fields are recorded with their `Display` implementation
+macro_rules! warning {
+    ($($field:ident = $value:expr,)* $message:literal) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::warn!($($field = %$value,)* $message);
+        #[cfg(not(feature = "tracing"))]
+        {
+            $(let _ = &$value;)*
+        }
+    };
+}
+
+macro_rules! counter {
+    ($name:literal $(, $label:literal => $value:expr)* $(,)?) => {
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!($name $(, $label => $value)*).increment(1);
+        #[cfg(not(feature = "metrics"))]
+        {
+            $(let _ = &$value;)*
+        }
+    };
+}
+
+macro_rules! histogram {
+    ($name:literal, $sample:expr $(, $label:literal => $value:expr)* $(,)?) => {
+        #[cfg(feature = "metrics")]
+        ::metrics::histogram!($name $(, $label => $value)*).record($sample as f64);
+        #[cfg(not(feature = "metrics"))]
+        {
+            let _ = &$sample;
+            $(let _ = &$value;)*
+        }
+    };
+}
+
+// only the Postgres subscription reports a gauge
+#[cfg(feature = "postgres")]
+macro_rules! gauge {
+    ($name:literal, $sample:expr $(, $label:literal => $value:expr)* $(,)?) => {
+        #[cfg(feature = "metrics")]
+        ::metrics::gauge!($name $(, $label => $value)*).set($sample as f64);
+        #[cfg(not(feature = "metrics"))]
+        {
+            let _ = &$sample;
+            $(let _ = &$value;)*
+        }
+    };
+}
+
+#[cfg(feature = "postgres")]
+pub(crate) use gauge;
+pub(crate) use {counter, histogram, span, warning};
+
+// wall clock time of a stage, recorded as `framework_duration_seconds`
+pub(crate) struct Timer {
+    #[cfg(feature = "metrics")]
+    started: std::time::Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(feature = "metrics")]
+            started: std::time::Instant::now(),
+        }
+    }
+
+    pub(crate) fn record(self, operation: &'static str, stage: &'static str, name: &'static str) {
+        #[cfg(feature = "metrics")]
+        ::metrics::histogram!(
+            "framework_duration_seconds",
+            "operation" => operation,
+            "stage" => stage,
+            "name" => name,
+        )
+        .record(self.started.elapsed().as_secs_f64());
+        #[cfg(not(feature = "metrics"))]
+        let _ = (operation, stage, name);
+    }
+}
+
+#[cfg(all(test, not(feature = "tracing"), not(feature = "metrics")))]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn disabled_telemetry_still_evaluates_arguments() {
+        let evaluated = Cell::new(0);
+        let value = || {
+            evaluated.set(evaluated.get() + 1);
+            1
+        };
+
+        let result = span!("test", id = value()).in_scope(|| 2);
+        assert_eq!(result, 2);
+        warning!(id = value(), "test");
+        counter!("test_total", "label" => value());
+        histogram!("test", value(), "label" => value());
+        #[cfg(feature = "postgres")]
+        gauge!("test", value(), "label" => value());
+
+        let timer = Timer::start();
+        timer.record("test", "stage", "name");
+
+        let expected = if cfg!(feature = "postgres") { 7 } else { 5 };
+        assert_eq!(evaluated.get(), expected);
+    }
+}
@@ -0,0 +1,1060 @@
+// Helpers for testing domain code and store implementations, checks panic on failure.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    fmt::{Debug, Write},
+    marker::PhantomData,
+    ops::Bound,
+};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aggregate::{Aggregate, AggregateTypeId},
+    codec::Codec,
+    command::{Command, ExpectedState},
+    error::{CommandError, FrameworkError},
+    event::{Event, EventStore, EventType, EventTypeId, VersionedEvent},
+    read_model::{Page, ReadModel, ReadModelScan, ReadModelStore, ReadModelUpdate},
+    repository::apply_events,
+    saga::{Saga, SagaStore, SagaTypeId},
+    snapshot::SnapshotStore,
+    Result,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TestEvent {
+    Added(u64),
+    Renamed(String),
+    Removed,
+}
+
+impl Event for TestEvent {
+    fn type_id(&self) -> EventTypeId {
+        match self {
+            TestEvent::Added(_) => 1,
+            TestEvent::Renamed(_) => 2,
+            TestEvent::Removed => 3,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            TestEvent::Added(_) => "Added",
+            TestEvent::Renamed(_) => "Renamed",
+            TestEvent::Removed => "Removed",
+        }
+    }
+
+    fn event_types() -> &'static [EventType] {
+        &[
+            EventType {
+                id: 1,
+                name: "Added",
+                revision: 1,
+            },
+            EventType {
+                id: 2,
+                name: "Renamed",
+                revision: 1,
+            },
+            EventType {
+                id: 3,
+                name: "Removed",
+                revision: 1,
+            },
+        ]
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TestCommand {
+    pub aggregate_id: u64,
+    pub event: TestEvent,
+}
+
+impl Command for TestCommand {
+    type Aggregate = TestAggregate;
+
+    fn aggregate_id(&self) -> u64 {
+        self.aggregate_id
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("Nothing to remove")]
+pub struct TestError;
+
+#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TestAggregate {
+    pub total: u64,
+    pub name: String,
+    pub removed: bool,
+}
+
+impl Aggregate for TestAggregate {
+    type Command = TestCommand;
+    type Event = TestEvent;
+    type Error = TestError;
+
+    fn type_id() -> AggregateTypeId {
+        0x7e57
+    }
+
+    fn type_name() -> &'static str {
+        "TestAggregate"
+    }
+
+    fn handle(&self, command: TestCommand) -> core::result::Result<Vec<TestEvent>, TestError> {
+        if command.event == TestEvent::Removed && self.total == 0 {
+            return Err(TestError);
+        }
+
+        Ok(vec![command.event])
+    }
+
+    fn apply_events(&mut self, events: Vec<TestEvent>) -> Result<()> {
+        for event in events {
+            match event {
+                TestEvent::Added(n) => self.total += n,
+                TestEvent::Renamed(name) => self.name = name,
+                TestEvent::Removed => self.removed = true,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestReadModel {
+    pub total: u64,
+    pub name: String,
+}
+
+impl ReadModel for TestReadModel {
+    type Event = TestEvent;
+
+    fn apply_event(&mut self, event: &TestEvent) -> Result<ReadModelUpdate> {
+        match event {
+            TestEvent::Added(n) => self.total += n,
+            TestEvent::Renamed(name) => self.name = name.clone(),
+            TestEvent::Removed => return Ok(ReadModelUpdate::Delete),
+        }
+
+        Ok(ReadModelUpdate::Upsert)
+    }
+}
+
+fn versions(events: Vec<VersionedEvent<TestEvent>>) -> Vec<(u32, TestEvent)> {
+    events.into_iter().map(|e| (e.version, e.event)).collect()
+}
+
+async fn read<E>(store: &E, aggregate_id: u64, from_version: u32) -> Vec<(u32, TestEvent)>
+where
+    E: EventStore,
+{
+    versions(
+        store
+            .read::<TestAggregate>(aggregate_id, from_version)
+            .await
+            .expect("read failed"),
+    )
+}
+
+// Conformance checks for store implementations, covering the behaviour `AggregateRepository`
+// and `Framework` rely on. Each check only touches the aggregate ids it is given, the
+// `*_conformance` functions run every check of a store trait against a fresh store.
+
+pub async fn event_store_conformance<E>(store: &E)
+where
+    E: EventStore,
+{
+    event_store_missing_stream(store, 1).await;
+    event_store_append_and_read(store, 2).await;
+    event_store_concurrency_conflict(store, 3).await;
+    event_store_stream_isolation(store, 4, 5).await;
+    event_store_large_batch(store, 6).await;
+    event_store_serde_round_trip(store, 7).await;
+    event_store_tombstone(store, 8, 9).await;
+}
+
+pub async fn event_store_missing_stream<E>(store: &E, aggregate_id: u64)
+where
+    E: EventStore,
+{
+    assert_eq!(read(store, aggregate_id, 0).await, vec![]);
+}
+
+pub async fn event_store_append_and_read<E>(store: &E, aggregate_id: u64)
+where
+    E: EventStore,
+{
+    let events = vec![
+        TestEvent::Added(1),
+        TestEvent::Renamed("a".to_string()),
+        TestEvent::Added(2),
+    ];
+    store
+        .save::<TestAggregate>(aggregate_id, 0, &events)
+        .await
+        .expect("save failed");
+
+    assert_eq!(
+        read(store, aggregate_id, 0).await,
+        vec![
+            (1, TestEvent::Added(1)),
+            (2, TestEvent::Renamed("a".to_string())),
+            (3, TestEvent::Added(2)),
+        ]
+    );
+    assert_eq!(
+        read(store, aggregate_id, 2).await,
+        vec![(3, TestEvent::Added(2))],
+        "events up to `from_version` must be skipped"
+    );
+    assert_eq!(read(store, aggregate_id, 3).await, vec![]);
+
+    store
+        .save::<TestAggregate>(aggregate_id, 3, &[TestEvent::Added(3), TestEvent::Removed])
+        .await
+        .expect("save failed");
+
+    assert_eq!(
+        read(store, aggregate_id, 2).await,
+        vec![
+            (3, TestEvent::Added(2)),
+            (4, TestEvent::Added(3)),
+            (5, TestEvent::Removed),
+        ]
+    );
+}
+
+pub async fn event_store_concurrency_conflict<E>(store: &E, aggregate_id: u64)
+where
+    E: EventStore,
+{
+    let result = store
+        .save::<TestAggregate>(aggregate_id, 1, &[TestEvent::Added(1)])
+        .await;
+    assert!(
+        matches!(result, Err(FrameworkError::ConcurrencyError)),
+        "saving a new stream at version 1 must conflict, got {:?}",
+        result
+    );
+
+    store
+        .save::<TestAggregate>(aggregate_id, 0, &[TestEvent::Added(1), TestEvent::Added(2)])
+        .await
+        .expect("save failed");
+
+    for expected_version in [0, 1, 3] {
+        let result = store
+            .save::<TestAggregate>(aggregate_id, expected_version, &[TestEvent::Added(9)])
+            .await;
+        assert!(
+            matches!(result, Err(FrameworkError::ConcurrencyError)),
+            "saving at version {} of a stream at version 2 must conflict, got {:?}",
+            expected_version,
+            result
+        );
+    }
+
+    assert_eq!(
+        read(store, aggregate_id, 0).await,
+        vec![(1, TestEvent::Added(1)), (2, TestEvent::Added(2))],
+        "conflicting saves must not append anything"
+    );
+}
+
+pub async fn event_store_stream_isolation<E>(store: &E, aggregate_id: u64, other_id: u64)
+where
+    E: EventStore,
+{
+    for version in 0..3 {
+        store
+            .save::<TestAggregate>(aggregate_id, version, &[TestEvent::Added(1)])
+            .await
+            .expect("save failed");
+        store
+            .save::<TestAggregate>(other_id, version, &[TestEvent::Added(2)])
+            .await
+            .expect("save failed");
+    }
+
+    assert_eq!(
+        read(store, aggregate_id, 0).await,
+        vec![
+            (1, TestEvent::Added(1)),
+            (2, TestEvent::Added(1)),
+            (3, TestEvent::Added(1)),
+        ]
+    );
+    assert_eq!(
+        read(store, other_id, 1).await,
+        vec![(2, TestEvent::Added(2)), (3, TestEvent::Added(2))]
+    );
+}
+
+pub async fn event_store_large_batch<E>(store: &E, aggregate_id: u64)
+where
+    E: EventStore,
+{
+    let events = (0..1000).map(TestEvent::Added).collect::<Vec<_>>();
+    store
+        .save::<TestAggregate>(aggregate_id, 0, &events)
+        .await
+        .expect("save failed");
+
+    let stored = read(store, aggregate_id, 0).await;
+    assert_eq!(stored.len(), events.len());
+    for (i, (version, event)) in stored.into_iter().enumerate() {
+        assert_eq!(version, i as u32 + 1);
+        assert_eq!(event, TestEvent::Added(i as u64));
+    }
+
+    assert_eq!(
+        read(store, aggregate_id, 990).await.first(),
+        Some(&(991, TestEvent::Added(990)))
+    );
+}
+
+pub async fn event_store_serde_round_trip<E>(store: &E, aggregate_id: u64)
+where
+    E: EventStore,
+{
+    let events = vec![
+        TestEvent::Added(u64::MAX),
+        TestEvent::Renamed(String::new()),
+        TestEvent::Renamed("\"quoted\" \\ \n \u{1F980} ünïcödé \0".to_string()),
+        TestEvent::Removed,
+    ];
+    store
+        .save::<TestAggregate>(aggregate_id, 0, &events)
+        .await
+        .expect("save failed");
+
+    assert_eq!(
+        read(store, aggregate_id, 0).await,
+        (1..).zip(events).collect::<Vec<_>>()
+    );
+}
+
+pub async fn event_store_tombstone<E>(store: &E, aggregate_id: u64, other_id: u64)
+where
+    E: EventStore,
+{
+    store
+        .save::<TestAggregate>(aggregate_id, 0, &[TestEvent::Added(1)])
+        .await
+        .expect("save failed");
+    store
+        .save::<TestAggregate>(other_id, 0, &[TestEvent::Added(2)])
+        .await
+        .expect("save failed");
+
+    store
+        .tombstone::<TestAggregate>(aggregate_id)
+        .await
+        .expect("tombstone failed");
+
+    let result = store.read::<TestAggregate>(aggregate_id, 0).await;
+    assert!(
+        matches!(result, Err(FrameworkError::AggregateDeleted(id)) if id == aggregate_id),
+        "reading a tombstoned stream must fail with `AggregateDeleted`"
+    );
+    let result = store
+        .save::<TestAggregate>(aggregate_id, 1, &[TestEvent::Added(1)])
+        .await;
+    assert!(
+        matches!(result, Err(FrameworkError::AggregateDeleted(id)) if id == aggregate_id),
+        "saving to a tombstoned stream must fail with `AggregateDeleted`, got {:?}",
+        result
+    );
+
+    assert_eq!(
+        read(store, other_id, 0).await,
+        vec![(1, TestEvent::Added(2))]
+    );
+}
+
+pub async fn snapshot_store_conformance<S>(store: &S)
+where
+    S: SnapshotStore,
+{
+    snapshot_store_round_trip(store, 1).await;
+    snapshot_store_delete(store, 2).await;
+}
+
+pub async fn snapshot_store_round_trip<S>(store: &S, aggregate_id: u64)
+where
+    S: SnapshotStore,
+{
+    let snapshot = store
+        .read::<TestAggregate>(aggregate_id)
+        .await
+        .expect("read failed");
+    assert_eq!(snapshot, None);
+
+    let aggregate = TestAggregate {
+        total: u64::MAX,
+        name: "\u{1F980} ünïcödé".to_string(),
+        removed: false,
+    };
+    store
+        .save(aggregate_id, 3, &aggregate)
+        .await
+        .expect("save failed");
+    let snapshot = store
+        .read::<TestAggregate>(aggregate_id)
+        .await
+        .expect("read failed");
+    assert_eq!(snapshot, Some((3, aggregate)));
+
+    let aggregate = TestAggregate {
+        total: 1,
+        name: String::new(),
+        removed: true,
+    };
+    store
+        .save(aggregate_id, 5, &aggregate)
+        .await
+        .expect("save failed");
+    let snapshot = store
+        .read::<TestAggregate>(aggregate_id)
+        .await
+        .expect("read failed");
+    assert_eq!(
+        snapshot,
+        Some((5, aggregate)),
+        "saving must replace the previous snapshot"
+    );
+}
+
+pub async fn snapshot_store_delete<S>(store: &S, aggregate_id: u64)
+where
+    S: SnapshotStore,
+{
+    store
+        .delete::<TestAggregate>(aggregate_id)
+        .await
+        .expect("deleting a missing snapshot must succeed");
+
+    store
+        .save(aggregate_id, 1, &TestAggregate::default())
+        .await
+        .expect("save failed");
+    store
+        .delete::<TestAggregate>(aggregate_id)
+        .await
+        .expect("delete failed");
+
+    let snapshot = store
+        .read::<TestAggregate>(aggregate_id)
+        .await
+        .expect("read failed");
+    assert_eq!(snapshot, None);
+}
+
+pub async fn read_model_store_conformance<S>(store: &S)
+where
+    S: ReadModelStore<ReadModel = TestReadModel>,
+{
+    read_model_store_round_trip(store, 1).await;
+    read_model_store_delete(store, 2).await;
+    read_model_store_update(store, 3).await;
+}
+
+pub async fn read_model_store_round_trip<S>(store: &S, id: u64)
+where
+    S: ReadModelStore<ReadModel = TestReadModel>,
+{
+    assert_eq!(store.read(id).await.expect("read failed"), None);
+    assert_eq!(store.position(id).await.expect("position failed"), 0);
+
+    let read_model = TestReadModel {
+        total: u64::MAX,
+        name: "\u{1F980} ünïcödé".to_string(),
+    };
+    store.save(id, 4, &read_model).await.expect("save failed");
+
+    assert_eq!(store.read(id).await.expect("read failed"), Some(read_model));
+    assert_eq!(store.position(id).await.expect("position failed"), 4);
+}
+
+pub async fn read_model_store_delete<S>(store: &S, id: u64)
+where
+    S: ReadModelStore<ReadModel = TestReadModel>,
+{
+    store
+        .save(id, 1, &TestReadModel::default())
+        .await
+        .expect("save failed");
+    store.delete(id, 2).await.expect("delete failed");
+
+    assert_eq!(store.read(id).await.expect("read failed"), None);
+    assert_eq!(
+        store.position(id).await.expect("position failed"),
+        2,
+        "deleted read models must keep their position"
+    );
+}
+
+pub async fn read_model_store_update<S>(store: &S, id: u64)
+where
+    S: ReadModelStore<ReadModel = TestReadModel>,
+{
+    store
+        .update_read_model(
+            id,
+            2,
+            &[TestEvent::Added(1), TestEvent::Renamed("a".to_string())],
+        )
+        .await
+        .expect("update failed");
+    store
+        .update_read_model(id, 3, &[TestEvent::Added(2)])
+        .await
+        .expect("update failed");
+
+    assert_eq!(
+        store.read(id).await.expect("read failed"),
+        Some(TestReadModel {
+            total: 3,
+            name: "a".to_string(),
+        })
+    );
+    assert_eq!(store.position(id).await.expect("position failed"), 3);
+
+    store
+        .update_read_model(id, 4, &[TestEvent::Removed])
+        .await
+        .expect("update failed");
+    assert_eq!(store.read(id).await.expect("read failed"), None);
+    assert_eq!(store.position(id).await.expect("position failed"), 4);
+
+    store
+        .update_read_model(id, 6, &[TestEvent::Removed, TestEvent::Added(5)])
+        .await
+        .expect("update failed");
+    assert_eq!(
+        store.read(id).await.expect("read failed"),
+        Some(TestReadModel {
+            total: 5,
+            name: String::new(),
+        }),
+        "events after a deletion must start from a fresh read model"
+    );
+}
+
+// expects a store without any read models
+pub async fn read_model_scan_conformance<S>(store: &S)
+where
+    S: ReadModelScan<ReadModel = TestReadModel>,
+{
+    for id in 1..=5 {
+        let read_model = TestReadModel {
+            total: id,
+            name: String::new(),
+        };
+        store.save(id, 1, &read_model).await.expect("save failed");
+    }
+    store.delete(3, 2).await.expect("delete failed");
+
+    let page = store.list(None, 2).await.expect("list failed");
+    let ids = page.items.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![1, 2]);
+    assert_eq!(page.next_cursor, Some(2));
+
+    let page = store.list(page.next_cursor, 2).await.expect("list failed");
+    let ids = page.items.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![4, 5], "deleted read models must be skipped");
+    assert_eq!(page.next_cursor, None);
+
+    let page = store
+        .scan(None, 10, |read_model| read_model.total % 2 == 0)
+        .await
+        .expect("scan failed");
+    let items = page
+        .items
+        .into_iter()
+        .map(|(id, m)| (id, m.total))
+        .collect::<Vec<_>>();
+    assert_eq!(items, vec![(2, 2), (4, 4)]);
+    assert_eq!(page.next_cursor, None);
+
+    // ids are unsigned, the upper half sorts after the lower one
+    for id in [u64::MAX, 1 << 63] {
+        let read_model = TestReadModel {
+            total: 0,
+            name: String::new(),
+        };
+        store.save(id, 1, &read_model).await.expect("save failed");
+    }
+
+    let page = store.list(Some(4), 2).await.expect("list failed");
+    let ids = page.items.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![5, 1 << 63]);
+    assert_eq!(page.next_cursor, Some(1 << 63));
+
+    let page = store.list(page.next_cursor, 2).await.expect("list failed");
+    let ids = page.items.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![u64::MAX]);
+    assert_eq!(page.next_cursor, None);
+
+    let page = store.list(Some(u64::MAX), 2).await.expect("list failed");
+    assert!(page.items.is_empty(), "nothing comes after the largest id");
+    assert_eq!(page.next_cursor, None);
+
+    for cursor in [None, Some(1)] {
+        assert!(
+            matches!(
+                store.list(cursor, 0).await,
+                Err(FrameworkError::InvalidScanLimit)
+            ),
+            "a limit of 0 must be refused"
+        );
+    }
+}
+
+// one line per event, `-` marks expected events that are missing and `+` unexpected ones
+fn diff<T>(expected: &[T], actual: &[T]) -> String
+where
+    T: Debug + PartialEq,
+{
+    let mut diff = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(expected), Some(actual)) if expected == actual => {
+                let _ = writeln!(diff, "  {}: {:?}", i, actual);
+            }
+            (expected, actual) => {
+                if let Some(expected) = expected {
+                    let _ = writeln!(diff, "- {}: {:?}", i, expected);
+                }
+                if let Some(actual) = actual {
+                    let _ = writeln!(diff, "+ {}: {:?}", i, actual);
+                }
+            }
+        }
+    }
+    diff
+}
+
+// given/when/then checks of a command handler, without any stores
+pub struct AggregateTestFixture<A>
+where
+    A: Aggregate,
+{
+    aggregate: Option<A>,
+}
+
+impl<A> AggregateTestFixture<A>
+where
+    A: Aggregate,
+{
+    // the aggregate is built from `events` the same way it is read from its stream,
+    // no events means it does not exist yet
+    pub fn given(events: Vec<A::Event>) -> Self {
+        if events.is_empty() {
+            return Self { aggregate: None };
+        }
+
+        let events = (1..)
+            .zip(events)
+            .map(|(version, event)| VersionedEvent { version, event })
+            .collect();
+
+        let mut aggregate = A::default();
+        if let Err(e) = apply_events(&mut 0, &mut aggregate, events) {
+            panic!("given events failed to apply: {}", e);
+        }
+
+        Self {
+            aggregate: Some(aggregate),
+        }
+    }
+
+    pub fn when(self, command: A::Command) -> AggregateTestResult<A> {
+        let aggregate_id = command.aggregate_id();
+
+        let result = match (command.expected_state(), self.aggregate) {
+            (ExpectedState::Exists, None) => {
+                Err(FrameworkError::AggregateNotFound(aggregate_id).into())
+            }
+            (ExpectedState::NotExists, Some(_)) => {
+                Err(FrameworkError::AggregateAlreadyExists(aggregate_id).into())
+            }
+            (_, aggregate) => aggregate
+                .unwrap_or_default()
+                .handle(command)
+                .map_err(CommandError::Domain),
+        };
+
+        AggregateTestResult { result }
+    }
+}
+
+pub struct AggregateTestResult<A>
+where
+    A: Aggregate,
+{
+    result: core::result::Result<Vec<A::Event>, CommandError<A::Error>>,
+}
+
+impl<A> AggregateTestResult<A>
+where
+    A: Aggregate,
+{
+    pub fn then_expect_events(self, expected: Vec<A::Event>)
+    where
+        A::Event: Debug + PartialEq,
+    {
+        match self.result {
+            Ok(events) if events == expected => {}
+            Ok(events) => panic!(
+                "unexpected events, - expected, + actual\n{}",
+                diff(&expected, &events)
+            ),
+            Err(e) => panic!(
+                "expected events, the command failed with {:?}\n{}",
+                e,
+                diff(&expected, &[])
+            ),
+        }
+    }
+
+    pub fn then_expect_error(self, expected: A::Error)
+    where
+        A::Event: Debug + PartialEq,
+        A::Error: PartialEq,
+    {
+        match self.result {
+            Err(CommandError::Domain(e)) if e == expected => {}
+            Err(e) => panic!("expected error {:?}, got {:?}", expected, e),
+            Ok(events) => panic!(
+                "expected error {:?}, the command emitted events\n{}",
+                expected,
+                diff(&[], &events)
+            ),
+        }
+    }
+
+    // for checks the `then_expect_*` functions don't cover, e.g. framework errors
+    pub fn into_result(self) -> core::result::Result<Vec<A::Event>, CommandError<A::Error>> {
+        self.result
+    }
+}
+
+// version and read model by id, `None` once deleted
+type ReadModels<M> = BTreeMap<u64, (u32, Option<M>)>;
+
+pub struct InMemoryReadModelStore<M>
+where
+    M: ReadModel + Clone,
+{
+    read_models: Mutex<ReadModels<M>>,
+}
+
+impl<M> Default for InMemoryReadModelStore<M>
+where
+    M: ReadModel + Clone,
+{
+    fn default() -> Self {
+        Self {
+            read_models: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl<M> InMemoryReadModelStore<M>
+where
+    M: ReadModel + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read_models(&self) -> Result<MutexGuard<'_, ReadModels<M>>> {
+        self.read_models
+            .lock()
+            .map_err(|e| FrameworkError::DatabaseError(e.to_string()))
+    }
+}
+
+impl<M> ReadModelStore for InMemoryReadModelStore<M>
+where
+    M: ReadModel + Clone,
+{
+    type ReadModel = M;
+
+    async fn read(&self, id: u64) -> Result<Option<M>> {
+        Ok(self.read_models()?.get(&id).and_then(|(_, x)| x.clone()))
+    }
+
+    async fn save(&self, id: u64, version: u32, read_model: &M) -> Result<()> {
+        self.read_models()?
+            .insert(id, (version, Some(read_model.clone())));
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: u64, version: u32) -> Result<()> {
+        self.read_models()?.insert(id, (version, None));
+
+        Ok(())
+    }
+
+    async fn position(&self, id: u64) -> Result<u32> {
+        Ok(self
+            .read_models()?
+            .get(&id)
+            .map_or(0, |(version, _)| *version))
+    }
+}
+
+impl<M> ReadModelScan for InMemoryReadModelStore<M>
+where
+    M: ReadModel + Clone,
+{
+    async fn scan<P>(&self, cursor: Option<u64>, limit: usize, predicate: P) -> Result<Page<M>>
+    where
+        P: Fn(&M) -> bool + Send,
+    {
+        if limit == 0 {
+            return Err(FrameworkError::InvalidScanLimit);
+        }
+
+        let read_models = self.read_models()?;
+
+        let start = cursor.map_or(Bound::Unbounded, Bound::Excluded);
+
+        let mut items = read_models
+            .range((start, Bound::Unbounded))
+            .filter_map(|(id, (_, x))| x.as_ref().map(|x| (*id, x)))
+            .filter(|(_, x)| predicate(x))
+            .take(limit + 1)
+            .map(|(id, x)| (id, x.clone()))
+            .collect::<Vec<_>>();
+
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|(id, _)| *id)
+        } else {
+            None
+        };
+
+        Ok(Page { items, next_cursor })
+    }
+}
+
+// version and encoded saga by saga type and correlation id
+type Sagas = BTreeMap<(SagaTypeId, u64), (u32, Vec<u8>)>;
+
+pub struct InMemorySagaStore<C>
+where
+    C: Codec,
+{
+    sagas: Mutex<Sagas>,
+    _codec: PhantomData<C>,
+}
+
+impl<C> Default for InMemorySagaStore<C>
+where
+    C: Codec,
+{
+    fn default() -> Self {
+        Self {
+            sagas: Mutex::new(BTreeMap::new()),
+            _codec: PhantomData,
+        }
+    }
+}
+
+impl<C> InMemorySagaStore<C>
+where
+    C: Codec,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sagas(&self) -> Result<MutexGuard<'_, Sagas>> {
+        self.sagas
+            .lock()
+            .map_err(|e| FrameworkError::DatabaseError(e.to_string()))
+    }
+}
+
+impl<C> SagaStore for InMemorySagaStore<C>
+where
+    C: Codec + Sync + Send + 'static,
+{
+    async fn read<S>(&self, correlation_id: u64) -> Result<Option<(u32, S)>>
+    where
+        S: Saga,
+    {
+        self.sagas()?
+            .get(&(S::type_id(), correlation_id))
+            .map(|(version, data)| Ok((*version, C::decode(data)?)))
+            .transpose()
+    }
+
+    async fn save<S>(&self, correlation_id: u64, expected_version: u32, saga: &S) -> Result<()>
+    where
+        S: Saga,
+    {
+        let data = C::encode(saga)?;
+        let mut sagas = self.sagas()?;
+
+        let key = (S::type_id(), correlation_id);
+        let version = sagas.get(&key).map_or(0, |(version, _)| *version);
+        if version != expected_version {
+            return Err(FrameworkError::ConcurrencyError);
+        }
+
+        sagas.insert(key, (version + 1, data));
+
+        Ok(())
+    }
+
+    async fn expired<S>(&self, now: u64) -> Result<Vec<u64>>
+    where
+        S: Saga,
+    {
+        let mut expired = Vec::new();
+        for ((type_id, correlation_id), (_, data)) in self.sagas()?.iter() {
+            if *type_id == S::type_id()
+                && C::decode::<S>(data)?
+                    .deadline()
+                    .is_some_and(|deadline| deadline <= now)
+            {
+                expired.push(*correlation_id);
+            }
+        }
+
+        Ok(expired)
+    }
+}
+
+// drives events through `ReadModelStore::update_read_model` against an in-memory store
+pub struct ReadModelTestFixture<M>
+where
+    M: ReadModel + Clone,
+{
+    batches: Vec<Vec<M::Event>>,
+}
+
+impl<M> ReadModelTestFixture<M>
+where
+    M: ReadModel + Clone,
+    M::Event: 'static,
+{
+    // events passed to `given` and each `when` are applied as one batch, like those of a command
+    pub fn given(events: Vec<M::Event>) -> Self {
+        Self {
+            batches: vec![events],
+        }
+    }
+
+    pub fn when(mut self, events: Vec<M::Event>) -> Self {
+        self.batches.push(events);
+        self
+    }
+
+    // `None` expects the read model to be deleted or never created
+    pub async fn then_expect(self, expected: Option<M>)
+    where
+        M: Debug + PartialEq,
+    {
+        let store = InMemoryReadModelStore::<M>::new();
+        let id = 1;
+
+        let mut version = 0;
+        for events in self.batches {
+            version += events.len() as u32;
+            if let Err(e) = store.update_read_model(id, version, &events).await {
+                panic!(
+                    "updating the read model failed at version {}: {}",
+                    version, e
+                );
+            }
+        }
+
+        let actual = store.read(id).await.expect("read failed");
+        if actual != expected {
+            panic!(
+                "unexpected read model\n- expected: {:#?}\n+ actual: {:#?}",
+                expected, actual
+            );
+        }
+
+        let position = store.position(id).await.expect("position failed");
+        assert_eq!(
+            position, version,
+            "the read model position must match the last event version"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn in_memory_read_model_store_conformance() {
+        read_model_store_conformance(&InMemoryReadModelStore::new()).await;
+        read_model_scan_conformance(&InMemoryReadModelStore::new()).await;
+    }
+
+    fn remove(aggregate_id: u64) -> TestCommand {
+        TestCommand {
+            aggregate_id,
+            event: TestEvent::Removed,
+        }
+    }
+
+    #[test]
+    fn fixture_expects_events() {
+        AggregateTestFixture::<TestAggregate>::given(vec![TestEvent::Added(2)])
+            .when(remove(1))
+            .then_expect_events(vec![TestEvent::Removed]);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected events, - expected, + actual")]
+    fn fixture_fails_on_other_events() {
+        AggregateTestFixture::<TestAggregate>::given(vec![TestEvent::Added(2)])
+            .when(remove(1))
+            .then_expect_events(vec![TestEvent::Added(1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected events, the command failed with")]
+    fn fixture_fails_on_error_instead_of_events() {
+        AggregateTestFixture::<TestAggregate>::given(vec![])
+            .when(remove(1))
+            .then_expect_events(vec![TestEvent::Removed]);
+    }
+
+    #[test]
+    fn fixture_expects_error() {
+        AggregateTestFixture::<TestAggregate>::given(vec![])
+            .when(remove(1))
+            .then_expect_error(TestError);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected error TestError, the command emitted events")]
+    fn fixture_fails_on_events_instead_of_error() {
+        AggregateTestFixture::<TestAggregate>::given(vec![TestEvent::Added(2)])
+            .when(remove(1))
+            .then_expect_error(TestError);
+    }
+}